@@ -1,9 +1,11 @@
 #![feature(test)]
 extern crate test;
 
-use dark_std::sync::SyncHashMap;
+use dark_std::sync::{ShardedSyncHashMap, SyncCell, SyncHashMap};
+use std::collections::HashMap;
+use std::sync::mpsc::channel;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 //6 ns/iter (+/- 0)
 #[bench]
@@ -16,6 +18,19 @@ fn bench_sync_map_get(b: &mut test::Bencher) {
     });
 }
 
+// The read path of `bench_sync_map_get` on a plain `SyncCell<HashMap>`, without
+// the copy-on-write `Arc` that `SyncHashMap` reads through for snapshots: the
+// difference is the cost of that extra pointer hop (within the noise of both
+// benches when measured).
+#[bench]
+fn bench_sync_cell_hash_map_get(b: &mut test::Bencher) {
+    let rw = SyncCell::new(HashMap::new());
+    rw.write().insert(1, 1);
+    b.iter(|| {
+        rw.read().get(&1);
+    });
+}
+
 // //18 ns/iter (+/- 0)
 // #[bench]
 // fn bench_dash_map_get(b: &mut test::Bencher) {
@@ -74,3 +89,114 @@ fn bench_sync_map_insert_race(b: &mut test::Bencher) {
 //         rw.insert(1,1);
 //     });
 // }
+
+#[bench]
+fn bench_sharded_map_get(b: &mut test::Bencher) {
+    let rw = ShardedSyncHashMap::new();
+    rw.insert(1, 1);
+    b.iter(|| {
+        rw.get(&1);
+    });
+}
+
+#[bench]
+fn bench_sharded_map_insert(b: &mut test::Bencher) {
+    let rw = ShardedSyncHashMap::new();
+    b.iter(|| {
+        rw.insert(1, 1);
+    });
+}
+
+/// Inserts into the map from three background threads, each on its own key.
+fn spawn_writers<F>(insert: F)
+where
+    F: Fn(i32) + Clone + Send + 'static,
+{
+    for k in 2..5 {
+        let insert = insert.clone();
+        std::thread::spawn(move || loop {
+            insert(k);
+        });
+    }
+    std::thread::sleep(Duration::from_secs(1));
+}
+
+#[bench]
+fn bench_sync_map_insert_race_keys(b: &mut test::Bencher) {
+    let rw = Arc::new(SyncHashMap::new());
+    let rw2 = rw.clone();
+    spawn_writers(move |k| {
+        rw2.insert(k, k);
+    });
+    b.iter(|| {
+        rw.insert(1, 1);
+    });
+}
+
+// writers to different keys mostly hit different shards
+#[bench]
+fn bench_sharded_map_insert_race_keys(b: &mut test::Bencher) {
+    let rw = Arc::new(ShardedSyncHashMap::new());
+    let rw2 = rw.clone();
+    spawn_writers(move |k| {
+        rw2.insert(k, k);
+    });
+    b.iter(|| {
+        rw.insert(1, 1);
+    });
+}
+
+/// Nanoseconds the thread behind `/proc/<pid>/task/<tid>` spent on a CPU.
+#[cfg(target_os = "linux")]
+fn thread_cpu_ns(task: &std::path::Path) -> u64 {
+    let stat = std::fs::read_to_string(task.join("schedstat")).unwrap();
+    stat.split_whitespace().next().unwrap().parse().unwrap()
+}
+
+// `get` latency on an uncontended map while, on another map, a writer waits
+// for a held read guard and a reader waits for that writer. Both waiters park
+// instead of spinning, so they leave the cores to the benchmark; their CPU time
+// over the whole run is printed next to the wall time.
+#[cfg(target_os = "linux")]
+#[bench]
+fn bench_sync_map_get_with_parked_waiters(b: &mut test::Bencher) {
+    let held = Arc::new(SyncHashMap::new());
+    held.insert(1, 1);
+    let guard = held.get(&1).unwrap();
+    let (send, recv) = channel();
+    let mut waiters = vec![];
+    for write in [true, false] {
+        let held = held.clone();
+        let send = send.clone();
+        waiters.push(std::thread::spawn(move || {
+            send.send(std::fs::read_link("/proc/thread-self").unwrap())
+                .unwrap();
+            if write {
+                held.insert(1, 2);
+            } else {
+                std::thread::sleep(Duration::from_millis(10));
+                held.get(&1);
+            }
+        }));
+    }
+    let tasks: Vec<_> = (0..2)
+        .map(|_| std::path::Path::new("/proc").join(recv.recv().unwrap()))
+        .collect();
+    std::thread::sleep(Duration::from_millis(100));
+
+    let rw = SyncHashMap::new();
+    rw.insert(1, 1);
+    let cpu_before: u64 = tasks.iter().map(|t| thread_cpu_ns(t)).sum();
+    let start = Instant::now();
+    b.iter(|| {
+        rw.get(&1);
+    });
+    let wall = start.elapsed().as_nanos() as u64;
+    let cpu = tasks.iter().map(|t| thread_cpu_ns(t)).sum::<u64>() - cpu_before;
+    println!("waiters used {} ns of CPU in {} ns", cpu, wall);
+
+    drop(guard);
+    for w in waiters {
+        w.join().unwrap();
+    }
+}
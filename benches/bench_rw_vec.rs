@@ -9,7 +9,7 @@ use std::sync::RwLock;
 fn bench_rw_vec(b: &mut test::Bencher) {
     let rw = RwLock::new(vec![1]);
     b.iter(|| {
-        let _a = rw.read().unwrap().get(0);
+        let _a = rw.read().unwrap().first();
     });
 }
 
@@ -50,13 +50,14 @@ fn bench_sync_vec_push(b: &mut test::Bencher) {
 
 //test bench_sync_vec_push ... bench:           15 ns/iter (+/- 1)
 #[bench]
+#[allow(clippy::needless_return)]
 fn bench_queue_push(b: &mut test::Bencher) {
-    let rw = crossbeam::queue::ArrayQueue::new(40000_0000);
+    let rw = crossbeam::queue::ArrayQueue::new(400_000_000);
     let mut i = 0;
     b.iter(|| {
         let _r = rw.push(i);
         i += 1;
-        if i == 40000_0000 - 2 {
+        if i == 400_000_000 - 2 {
             return;
         }
     });
@@ -68,7 +69,7 @@ fn bench_queue2_push(b: &mut test::Bencher) {
     let rw = crossbeam::queue::SegQueue::new();
     let mut i = 0;
     b.iter(|| {
-        let _r = rw.push(i);
+        rw.push(i);
         i += 1;
     });
 }
@@ -76,10 +77,10 @@ fn bench_queue2_push(b: &mut test::Bencher) {
 // test bench_queue_push    ... bench:          13 ns/iter (+/- 1)
 #[bench]
 fn bench_channel_send(b: &mut test::Bencher) {
-    let (send, rev) = crossbeam::channel::unbounded();
+    let (send, _rev) = crossbeam::channel::unbounded();
     let mut i = 0;
     b.iter(|| {
-        let _r = send.send(i).unwrap();
+        send.send(i).unwrap();
         i += 1;
     });
 }
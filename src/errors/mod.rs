@@ -23,6 +23,7 @@ impl Error {
         }
     }
 
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(&self) -> String {
         self.inner.clone()
     }
@@ -77,43 +78,43 @@ impl std::error::Error for Error {}
 
 impl From<&str> for Error {
     fn from(arg: &str) -> Self {
-        return new(arg.to_string());
+        new(arg.to_string())
     }
 }
 
 impl From<std::string::String> for Error {
     fn from(arg: String) -> Self {
-        return new(arg);
+        new(arg)
     }
 }
 
 impl From<&dyn std::error::Error> for Error {
     fn from(arg: &dyn std::error::Error) -> Self {
-        return new(arg.to_string());
+        new(arg.to_string())
     }
 }
 
 impl From<Box<dyn std::error::Error>> for Error {
     fn from(arg: Box<dyn std::error::Error>) -> Self {
-        return new(arg.to_string());
+        new(arg.to_string())
     }
 }
 
 impl From<&Box<dyn std::error::Error>> for Error {
     fn from(arg: &Box<dyn std::error::Error>) -> Self {
-        return new(arg.to_string());
+        new(arg.to_string())
     }
 }
 
 impl From<std::sync::mpsc::RecvError> for Error {
     fn from(e: RecvError) -> Self {
-        return new(e.to_string());
+        new(e.to_string())
     }
 }
 
 impl<T> From<std::sync::mpsc::SendError<T>> for Error {
     fn from(e: std::sync::mpsc::SendError<T>) -> Self {
-        return new(e.to_string());
+        new(e.to_string())
     }
 }
 
@@ -0,0 +1,303 @@
+use serde::{Deserializer, Serialize, Serializer};
+use std::cell::UnsafeCell;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{
+    OwnedReadGuard, RawLock, ReadLock, ReadMapGuard, SendReadMapGuard, UpgradableLock,
+    UpgradableReadGuard, WriteGuard, WriteLock,
+};
+use crate::errors::Error;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+/// A generic cell that protects any `T` with the same read/write-separation
+/// protocol as `SyncHashMap`, `SyncBtreeMap`, `SyncIndexMap` and `SyncVec`.
+///
+/// Reads are lock-free: [`SyncCell::read`] only registers a reader slot in the
+/// calling thread's private counter, so concurrent readers never block each
+/// other and never touch a lock word. Writes ([`SyncCell::write`]) take a
+/// mutex, raise a `writing` flag and wait until all in-flight readers are gone
+/// before handing out a mutable reference.
+///
+/// Use it to guard your own structures (a `VecDeque`, a `HashSet`, a routing
+/// table...) instead of wrapping them in an `RwLock`:
+///
+/// ```
+/// use dark_std::sync::SyncCell;
+/// use std::collections::HashSet;
+///
+/// let set = SyncCell::new(HashSet::new());
+/// set.write().insert(1);
+/// assert!(set.read().contains(&1));
+/// ```
+///
+/// # Deadlock note
+/// A read guard makes writers wait until it is dropped. Do not call
+/// [`SyncCell::write`] while a guard is alive in the same scope: drop the
+/// guard first, otherwise the writer waits for its own guard and deadlocks.
+/// Debug builds detect this and panic with a message naming the container;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy) to configure it.
+pub struct SyncCell<T> {
+    raw: RawLock,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: all writers hold the writer lock and wait for readers to drain
+// before touching `value`; readers either see a consistent value or retry
+// while a writer is active, so concurrent access to `value` is race-free.
+unsafe impl<T: Send> Send for SyncCell<T> {}
+unsafe impl<T: Send + Sync> Sync for SyncCell<T> {}
+
+impl<T> SyncCell<T> {
+    pub fn new(value: T) -> Self {
+        Self::named(value, "SyncCell")
+    }
+
+    /// A cell whose diagnostics (e.g. deadlock panics) name the wrapping
+    /// container type instead of `SyncCell`.
+    pub(crate) fn named(value: T, name: &'static str) -> Self {
+        Self {
+            raw: RawLock::new(name),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn new_arc(value: T) -> Arc<Self> {
+        Arc::new(Self::new(value))
+    }
+
+    /// Returns a read guard to the whole value.
+    ///
+    /// The read is lock-free: it only registers a reader slot, so concurrent
+    /// reads never block each other and never take a lock. Writers wait for
+    /// the returned guard to be dropped before mutating the value.
+    #[inline]
+    pub fn read(&self) -> ReadMapGuard<'_, T> {
+        let r = self.begin_read();
+        ReadMapGuard::new(r, unsafe { &*self.value.get() })
+    }
+
+    /// `Send` version of [`SyncCell::read`]: the guard can be held across an
+    /// `.await` and dropped on any thread. See [`SendReadMapGuard`].
+    #[inline]
+    pub fn read_send(&self) -> SendReadMapGuard<'_, T> {
+        let r = self.begin_read_send();
+        SendReadMapGuard::new(r, unsafe { &*self.value.get() })
+    }
+
+    /// Owned version of [`SyncCell::read`] on an `Arc`-wrapped cell: the guard
+    /// keeps the cell alive instead of borrowing it. See [`OwnedReadGuard`].
+    pub fn read_owned(self: &Arc<Self>) -> OwnedReadGuard<Self, T> {
+        let r = self.begin_read_send();
+        unsafe { OwnedReadGuard::new(self.clone(), r, &*self.value.get()) }
+    }
+
+    /// Returns an upgradable read guard to the whole value: it coexists with
+    /// plain readers, keeps other writers out and can be turned into a write
+    /// guard with [`UpgradableReadGuard::upgrade`].
+    pub fn upgradable_read(&self) -> UpgradableReadGuard<'_, T> {
+        let u = self.begin_upgradable();
+        UpgradableReadGuard::new(u, self.value.get())
+    }
+
+    /// Returns a write guard to the whole value.
+    ///
+    /// The guard holds the writer lock (writers are mutually exclusive and
+    /// wait for in-flight readers) until it is dropped, so the mutable
+    /// reference can never race with concurrent readers or writers.
+    #[inline]
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        let w = self.begin_write();
+        WriteGuard::new(w, unsafe { &mut *self.value.get() })
+    }
+
+    /// Non-blocking version of [`SyncCell::write`]: returns a `WouldBlock`
+    /// error instead of waiting when another writer holds the lock or readers
+    /// are active.
+    pub fn try_write(&self) -> Result<WriteGuard<'_, T>, Error> {
+        let w = self.try_begin_write()?;
+        Ok(WriteGuard::new(w, unsafe { &mut *self.value.get() }))
+    }
+
+    /// Bounded version of [`SyncCell::write`]: returns a `Timeout` error if
+    /// the writer lock cannot be taken or readers do not drain within
+    /// `timeout`, instead of hanging on a leaked read guard.
+    pub fn write_timeout(&self, timeout: Duration) -> Result<WriteGuard<'_, T>, Error> {
+        let w = self.begin_write_timeout(timeout)?;
+        Ok(WriteGuard::new(w, unsafe { &mut *self.value.get() }))
+    }
+
+    /// Returns the default write timeout, see
+    /// [`SyncCell::set_default_write_timeout`].
+    pub fn default_write_timeout(&self) -> Option<Duration> {
+        self.raw.default_write_timeout()
+    }
+
+    /// Makes [`SyncCell::try_write`] (and the containers' `try_*` writes)
+    /// wait up to `timeout` for the writer lock and the reader drain, then
+    /// return a `Timeout` error, instead of failing at once with
+    /// `WouldBlock`. Blocking writes such as [`SyncCell::write`] are not
+    /// affected and still wait forever. `None` (the default) turns it off.
+    pub fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.raw.set_default_write_timeout(timeout)
+    }
+
+    /// Contention counters of this cell: reads, writes, writer wait times,
+    /// reader retries and reader-slot occupancy. Requires the `stats` cargo
+    /// feature; without it the counters are compiled out.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.raw.stats()
+    }
+
+    /// Async version of [`SyncCell::read`]: awaits the end of an active write
+    /// instead of yielding the OS thread.
+    pub async fn read_async(&self) -> ReadMapGuard<'_, T> {
+        let r = self.begin_read_async().await;
+        ReadMapGuard::new(r, unsafe { &*self.value.get() })
+    }
+
+    /// Async version of [`SyncCell::write`]: awaits the writer lock and the
+    /// reader drain instead of blocking the OS thread.
+    pub async fn write_async(&self) -> WriteGuard<'_, T> {
+        let w = self.begin_write_async().await;
+        WriteGuard::new(w, unsafe { &mut *self.value.get() })
+    }
+
+    /// Returns a mutable reference to the value. No locking is needed: the
+    /// `&mut self` borrow guarantees there are no other readers or writers.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    #[inline]
+    pub(crate) fn begin_read(&self) -> ReadLock<'_> {
+        self.raw.begin_read()
+    }
+
+    #[inline]
+    pub(crate) fn begin_read_send(&self) -> ReadLock<'_> {
+        self.raw.begin_read_send()
+    }
+
+    #[inline]
+    pub(crate) fn begin_upgradable(&self) -> UpgradableLock<'_> {
+        self.raw.begin_upgradable()
+    }
+
+    #[inline]
+    pub(crate) fn begin_write(&self) -> WriteLock<'_> {
+        self.raw.begin_write()
+    }
+
+    /// See [`RawLock::begin_write_with`].
+    #[inline]
+    pub(crate) fn begin_write_with(&self, prepare: impl FnOnce()) -> WriteLock<'_> {
+        self.raw.begin_write_with(prepare)
+    }
+
+    #[inline]
+    pub(crate) fn try_begin_write(&self) -> Result<WriteLock<'_>, Error> {
+        self.raw.try_begin_write()
+    }
+
+    #[inline]
+    pub(crate) fn try_begin_write_with(
+        &self,
+        prepare: impl FnOnce(),
+    ) -> Result<WriteLock<'_>, Error> {
+        self.raw.try_begin_write_with(prepare)
+    }
+
+    #[inline]
+    pub(crate) fn begin_write_timeout(&self, timeout: Duration) -> Result<WriteLock<'_>, Error> {
+        self.raw.begin_write_timeout(timeout)
+    }
+
+    #[inline]
+    pub(crate) fn begin_write_timeout_with(
+        &self,
+        timeout: Duration,
+        prepare: impl FnOnce(),
+    ) -> Result<WriteLock<'_>, Error> {
+        self.raw.begin_write_timeout_with(timeout, prepare)
+    }
+
+    pub(crate) async fn begin_read_async(&self) -> ReadLock<'_> {
+        self.raw.begin_read_async().await
+    }
+
+    pub(crate) async fn begin_write_async(&self) -> WriteLock<'_> {
+        self.raw.begin_write_async().await
+    }
+
+    pub(crate) async fn begin_write_async_with(&self, prepare: impl FnOnce()) -> WriteLock<'_> {
+        self.raw.begin_write_async_with(prepare).await
+    }
+
+    /// Raw pointer to the protected value. Dereferencing it is only sound
+    /// while holding a [`ReadLock`] (shared) or a [`WriteLock`] (exclusive).
+    #[inline]
+    pub(crate) fn data_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+impl<T> From<T> for SyncCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Serialize> Serialize for SyncCell<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.read().serialize(serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for SyncCell<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v = T::deserialize(deserializer)?;
+        Ok(Self::new(v))
+    }
+}
+
+impl<T: Debug> Debug for SyncCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.read(), f)
+    }
+}
+
+impl<T: Display> Display for SyncCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&*self.read(), f)
+    }
+}
+
+impl<T: Clone> Clone for SyncCell<T> {
+    fn clone(&self) -> Self {
+        SyncCell::new(self.read().clone())
+    }
+}
+
+impl<T: Default> Default for SyncCell<T> {
+    fn default() -> Self {
+        SyncCell::new(T::default())
+    }
+}
@@ -0,0 +1,120 @@
+use parking_lot::Mutex;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use super::{SyncCell, WriteLock};
+use crate::errors::Error;
+
+/// Copy-on-write storage behind the maps that hand out O(1) snapshots.
+///
+/// The data sits in an `Arc`: a snapshot is one more reference to it, and the
+/// next write copies the data first if a snapshot still holds one, so
+/// snapshots never make writers wait. The copy function is recorded by the
+/// first `snapshot` (the only method that needs `M: Clone`); until then the
+/// `Arc` is never shared and writes go straight to the data.
+///
+/// Writers take the copy in [`CopyOnWrite::copy_ahead`], before they hold off
+/// readers (see the `begin_cow_write*` methods), so readers keep going while
+/// the data is copied; `get_mut` then only swaps it in.
+pub(crate) struct CopyOnWrite<M> {
+    data: Arc<M>,
+    clone: OnceLock<fn(&M) -> M>,
+    // The copy made by `copy_ahead`, for the next `get_mut`.
+    ahead: Mutex<Option<M>>,
+}
+
+impl<M> CopyOnWrite<M> {
+    pub(crate) fn new(data: M) -> Self {
+        CopyOnWrite {
+            data: Arc::new(data),
+            clone: OnceLock::new(),
+            ahead: Mutex::new(None),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> &M {
+        &self.data
+    }
+
+    /// The data, copied first if a snapshot still shares it.
+    #[inline]
+    pub(crate) fn get_mut(&mut self) -> &mut M {
+        let ahead = self.ahead.get_mut().take();
+        if Arc::get_mut(&mut self.data).is_none() {
+            let data = ahead.unwrap_or_else(|| self.copy());
+            self.data = Arc::new(data);
+        }
+        Arc::get_mut(&mut self.data).expect("unshared")
+    }
+
+    /// Copies the data for the next [`CopyOnWrite::get_mut`] if a snapshot
+    /// shares it. Only reads the data, so it may run alongside readers, but
+    /// the caller must hold the writer lock so the data cannot change before
+    /// that `get_mut`.
+    #[inline]
+    pub(crate) fn copy_ahead(&self) {
+        if Arc::strong_count(&self.data) == 1 {
+            return;
+        }
+        let mut ahead = self.ahead.lock();
+        if ahead.is_none() {
+            *ahead = Some(self.copy());
+        }
+    }
+
+    fn copy(&self) -> M {
+        (self.clone.get().expect("shared without a snapshot"))(&self.data)
+    }
+
+    pub(crate) fn snapshot(&self) -> Arc<M>
+    where
+        M: Clone,
+    {
+        self.clone.get_or_init(|| M::clone);
+        self.data.clone()
+    }
+
+    pub(crate) fn into_inner(mut self) -> M {
+        if let Some(ahead) = self.ahead.get_mut().take() {
+            return ahead;
+        }
+        match Arc::try_unwrap(self.data) {
+            Ok(data) => data,
+            Err(data) => (self.clone.get().expect("shared without a snapshot"))(&data),
+        }
+    }
+}
+
+/// The write entry points of the copy-on-write maps: like the plain
+/// `begin_write*`, but a map shared with a snapshot is copied before readers
+/// are held off.
+impl<M> SyncCell<CopyOnWrite<M>> {
+    #[inline]
+    pub(crate) fn begin_cow_write(&self) -> WriteLock<'_> {
+        self.begin_write_with(|| self.copy_ahead())
+    }
+
+    #[inline]
+    pub(crate) fn try_begin_cow_write(&self) -> Result<WriteLock<'_>, Error> {
+        self.try_begin_write_with(|| self.copy_ahead())
+    }
+
+    #[inline]
+    pub(crate) fn begin_cow_write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<WriteLock<'_>, Error> {
+        self.begin_write_timeout_with(timeout, || self.copy_ahead())
+    }
+
+    pub(crate) async fn begin_cow_write_async(&self) -> WriteLock<'_> {
+        self.begin_write_async_with(|| self.copy_ahead()).await
+    }
+
+    fn copy_ahead(&self) {
+        // SAFETY: only called with the writer lock held, before readers are
+        // held off: `copy_ahead` only reads, like they do.
+        unsafe { &*self.data_ptr() }.copy_ahead()
+    }
+}
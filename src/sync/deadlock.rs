@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// What a blocking or `try_*` write does when the calling thread still holds a
+/// read guard on the same container, i.e. when the write would wait for its
+/// own guard forever.
+///
+/// Async writes are never checked: another task on the same thread may hold
+/// the guard and release it while the writer awaits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadlockPolicy {
+    /// Panic with a message naming the container type. The default in debug
+    /// builds. `try_*` and `*_timeout` writes are not checked: they give up
+    /// with `WouldBlock` or `Timeout` on their own.
+    Panic,
+    /// `try_*` and `*_timeout` writes return a `Deadlock` error; blocking
+    /// writes, which cannot return an error, panic.
+    Error,
+    /// Do not check: the write waits (forever, unless a timeout is set). The
+    /// default in release builds, where the check costs one atomic load.
+    Block,
+}
+
+const PANIC: u8 = 0;
+const ERROR: u8 = 1;
+const BLOCK: u8 = 2;
+
+static POLICY: AtomicU8 = AtomicU8::new(if cfg!(debug_assertions) { PANIC } else { BLOCK });
+
+/// Sets the process-wide [`DeadlockPolicy`] for every Sync container.
+pub fn set_deadlock_policy(policy: DeadlockPolicy) {
+    let v = match policy {
+        DeadlockPolicy::Panic => PANIC,
+        DeadlockPolicy::Error => ERROR,
+        DeadlockPolicy::Block => BLOCK,
+    };
+    POLICY.store(v, Ordering::Relaxed);
+}
+
+/// Returns the current process-wide [`DeadlockPolicy`].
+pub fn deadlock_policy() -> DeadlockPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        PANIC => DeadlockPolicy::Panic,
+        ERROR => DeadlockPolicy::Error,
+        _ => DeadlockPolicy::Block,
+    }
+}
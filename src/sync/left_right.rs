@@ -0,0 +1,293 @@
+use parking_lot::Mutex;
+use std::cell::UnsafeCell;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::wait::WaitList;
+use super::{check_reentrant_write, reader_count_for, Registry, CONTAINER_ID};
+
+/// A change that can be replayed on either copy of a [`LeftRight`].
+pub(crate) trait Absorb<O> {
+    fn absorb(&mut self, op: O);
+}
+
+/// The left-right (double-buffered) engine behind `LeftRightHashMap` and
+/// `LeftRightBtreeMap`.
+///
+/// It keeps two copies of the data. Readers always read the copy selected by
+/// `left_right` and never wait: they only register on one of two reader
+/// indicators (per-thread counters, as in [`RawLock`](super::RawLock)),
+/// chosen by `version`. A writer applies its changes to the other copy while
+/// recording them in an operation log, publishes that copy by flipping
+/// `left_right`, waits until both indicators have drained of readers that may
+/// still see the old copy, and replays the log on it. Writers are serialized
+/// and are the only side that ever waits. A write that panics is not
+/// published: `clone` resets its copy from the published one instead.
+pub(crate) struct LeftRight<C> {
+    copies: [UnsafeCell<C>; 2],
+    // The copy readers read.
+    left_right: AtomicUsize,
+    // The indicator new readers arrive on.
+    version: AtomicUsize,
+    ids: [usize; 2],
+    indicators: [Arc<Registry>; 2],
+    write: Mutex<()>,
+    waiters: WaitList,
+    // Container type name, for deadlock diagnostics.
+    name: &'static str,
+    clone: fn(&C) -> C,
+}
+
+// SAFETY: readers only get `&C` to the published copy; the unpublished copy
+// is only touched by the writer holding `write`, after the readers that could
+// still see it have drained.
+unsafe impl<C: Send> Send for LeftRight<C> {}
+unsafe impl<C: Send + Sync> Sync for LeftRight<C> {}
+
+impl<C> LeftRight<C> {
+    pub(crate) fn new(data: C, name: &'static str) -> Self
+    where
+        C: Clone,
+    {
+        LeftRight {
+            copies: [UnsafeCell::new(data.clone()), UnsafeCell::new(data)],
+            left_right: AtomicUsize::new(0),
+            version: AtomicUsize::new(0),
+            ids: [
+                CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
+                CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
+            ],
+            indicators: [Arc::new(Registry::new()), Arc::new(Registry::new())],
+            write: Mutex::new(()),
+            waiters: WaitList::new(),
+            name,
+            clone: C::clone,
+        }
+    }
+
+    /// Registers the calling thread as a reader and returns the published
+    /// copy. Never waits.
+    #[inline]
+    pub(crate) fn read(&self) -> (ReadToken<'_>, &C) {
+        let vi = self.version.load(Ordering::SeqCst);
+        let count = reader_count_for(self.ids[vi], &self.indicators[vi]);
+        count.fetch_add(1, Ordering::SeqCst);
+        let lr = self.left_right.load(Ordering::SeqCst);
+        // SAFETY: the writer never touches the copy selected by `left_right`
+        // before this reader's indicator has drained.
+        let data = unsafe { &*self.copies[lr].get() };
+        (
+            ReadToken {
+                count,
+                waiters: &self.waiters,
+            },
+            data,
+        )
+    }
+
+    /// Runs `f` on the unpublished copy, then publishes it and replays the
+    /// operations `f` logged on the other copy. `f` must apply every change it
+    /// makes to the copy and push the same change to the log.
+    ///
+    /// Panics if the calling thread holds a read guard on this container and
+    /// the [`DeadlockPolicy`](super::DeadlockPolicy) is not `Block`.
+    pub(crate) fn write<O, R, F>(&self, f: F) -> R
+    where
+        C: Absorb<O>,
+        F: FnOnce(&mut C, &mut Vec<O>) -> R,
+    {
+        if let Err(e) = check_reentrant_write(self.name, &self.ids) {
+            panic!("{}", e);
+        }
+        let _w = self.write.lock();
+        let lr = self.left_right.load(Ordering::SeqCst);
+        // If `f` panics, some of its changes may be missing from the log:
+        // `Publish` then resets the copy instead of publishing it.
+        let mut publish = Publish {
+            lr: self,
+            log: Vec::new(),
+            read: lr,
+            completed: false,
+        };
+        // SAFETY: no reader sees the unpublished copy (the previous write
+        // drained them all before returning), and `write` keeps other writers
+        // out.
+        let data = unsafe { &mut *self.copies[1 - lr].get() };
+        let r = f(data, &mut publish.log);
+        publish.completed = true;
+        r
+    }
+
+    /// Waits until no reader is left on the copy that was published before
+    /// `left_right` was flipped.
+    fn drain_readers(&self) {
+        let prev = self.version.load(Ordering::SeqCst);
+        let next = 1 - prev;
+        // Readers still on `next` arrived before the previous toggle.
+        self.waiters.block_until(|| self.indicators[next].drained());
+        self.version.store(next, Ordering::SeqCst);
+        self.waiters.block_until(|| self.indicators[prev].drained());
+    }
+
+    pub(crate) fn into_inner(self) -> C {
+        let [left, right] = self.copies;
+        if self.left_right.load(Ordering::Relaxed) == 0 {
+            left.into_inner()
+        } else {
+            right.into_inner()
+        }
+    }
+}
+
+/// Publishes the copy written by [`LeftRight::write`] and brings the other
+/// copy up to date when dropped, or, if the write panicked, resets the
+/// written copy to the published one.
+struct Publish<'a, C: Absorb<O>, O> {
+    lr: &'a LeftRight<C>,
+    log: Vec<O>,
+    // The copy readers were reading when the write started.
+    read: usize,
+    // Set once `f` returned. `thread::panicking` alone is not enough: it is
+    // also true for a write made from a destructor during an unrelated unwind.
+    completed: bool,
+}
+
+impl<'a, C: Absorb<O>, O> Drop for Publish<'a, C, O> {
+    fn drop(&mut self) {
+        let lr = self.lr;
+        if !self.completed {
+            // SAFETY: no reader sees the unpublished copy, and the published
+            // one is only read.
+            unsafe {
+                let published = &*lr.copies[self.read].get();
+                *lr.copies[1 - self.read].get() = (lr.clone)(published);
+            }
+            return;
+        }
+        if self.log.is_empty() {
+            return;
+        }
+        lr.left_right.store(1 - self.read, Ordering::SeqCst);
+        lr.drain_readers();
+        // SAFETY: every reader that could see this copy has left.
+        let data = unsafe { &mut *lr.copies[self.read].get() };
+        for op in self.log.drain(..) {
+            data.absorb(op);
+        }
+    }
+}
+
+/// Internal RAII token of a left-right reader: holds one count in a reader
+/// indicator and releases it when dropped.
+pub(crate) struct ReadToken<'a> {
+    count: &'a AtomicUsize,
+    waiters: &'a WaitList,
+}
+
+impl<'a> Drop for ReadToken<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        // Only the last reader on an indicator can let a draining writer go;
+        // `notify_all` is a single load when no writer is parked.
+        if self.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.waiters.notify_all();
+        }
+    }
+}
+
+/// A read guard returned by the `get` and `read` methods of the left-right
+/// maps.
+///
+/// Taking it never waits, even while a writer is active. Like every read guard
+/// it delays the next writer until it is dropped, and it is not `Send`: it
+/// must be dropped on the same thread that created it.
+pub struct LeftRightGuard<'a, V> {
+    _r: ReadToken<'a>,
+    value: NonNull<V>,
+    _marker: PhantomData<&'a V>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, V> LeftRightGuard<'a, V> {
+    #[inline]
+    pub(crate) fn new(_r: ReadToken<'a>, value: &'a V) -> Self {
+        LeftRightGuard {
+            _r,
+            value: NonNull::from(value),
+            _marker: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'a, V> Deref for LeftRightGuard<'a, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the token held by the guard keeps the writer off this copy.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<'a, V: Debug> Debug for LeftRightGuard<'a, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, V: Display> Display for LeftRightGuard<'a, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, V: PartialEq> PartialEq for LeftRightGuard<'a, V> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, V: Eq> Eq for LeftRightGuard<'a, V> {}
+
+impl<'a, V: PartialEq> PartialEq<V> for LeftRightGuard<'a, V> {
+    fn eq(&self, other: &V) -> bool {
+        **self == *other
+    }
+}
+
+impl<'a, V: PartialEq> PartialEq<&V> for LeftRightGuard<'a, V> {
+    fn eq(&self, other: &&V) -> bool {
+        **self == **other
+    }
+}
+
+/// Read iterator returned by the `iter` methods of the left-right maps.
+pub struct LeftRightIter<'a, I> {
+    _r: ReadToken<'a>,
+    inner: I,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, I> LeftRightIter<'a, I> {
+    #[inline]
+    pub(crate) fn new(_r: ReadToken<'a>, inner: I) -> Self {
+        LeftRightIter {
+            _r,
+            inner,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'a, I: Iterator> Iterator for LeftRightIter<'a, I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
@@ -1,19 +1,27 @@
-use parking_lot::Mutex;
 use serde::{Deserializer, Serialize, Serializer};
 use std::borrow::Borrow;
-use std::cell::UnsafeCell;
 use std::collections::{
-    btree_map::IntoIter as MapIntoIter, btree_map::Iter as MapIter,
-    btree_map::IterMut as MapIterMut, BTreeMap,
+    btree_map, btree_map::IntoIter as MapIntoIter, btree_map::Iter as MapIter,
+    btree_map::IterMut as MapIterMut, btree_map::Range as MapRange,
+    btree_map::RangeMut as MapRangeMut, BTreeMap,
 };
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut, Index};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::ops::{Bound, Deref, DerefMut, Index, RangeBounds};
 use std::sync::Arc;
 
-use super::{ReadGuard, ReadMapGuard, WriteGuard, WriteLock};
+use crate::errors::Error;
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::cow::CopyOnWrite;
+use super::{
+    OwnedIter, OwnedReadGuard, ReadEntryGuard, ReadGuard, ReadLock, ReadMapGuard, SendReadGuard,
+    SendReadMapGuard, SyncCell, Transaction, UpgradableReadGuard, WriteGuard, WriteLock,
+};
 
 /// Read guard returned by [`SyncBtreeMap::get`].
 pub type BtreeMapGet<'a, V> = ReadGuard<'a, V>;
@@ -72,17 +80,11 @@ impl<'a, K, V: Eq> Eq for BtreeMapRefMut<'a, K, V> {}
 
 /// Read iterator returned by [`SyncBtreeMap::iter`].
 pub struct BtreeMapIter<'a, K, V> {
-    count: &'a AtomicUsize,
+    _r: ReadLock<'a>,
     inner: MapIter<'a, K, V>,
     _not_send: PhantomData<*const ()>,
 }
 
-impl<'a, K, V> Drop for BtreeMapIter<'a, K, V> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
-    }
-}
-
 impl<'a, K, V> Iterator for BtreeMapIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
@@ -91,6 +93,20 @@ impl<'a, K, V> Iterator for BtreeMapIter<'a, K, V> {
     }
 }
 
+/// Owned read guard returned by [`SyncBtreeMap::get_owned`].
+pub type BtreeMapGetOwned<K, V> = OwnedReadGuard<SyncBtreeMap<K, V>, V>;
+
+/// Owned iterator returned by [`SyncBtreeMap::iter_owned`].
+pub type BtreeMapIterOwned<K, V> = OwnedIter<SyncBtreeMap<K, V>, MapIter<'static, K, V>>;
+
+impl<K: Eq + Hash + Clone, V: Clone> Iterator for BtreeMapIterOwned<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner().next().map(|(k, v)| (k.clone(), v.clone()))
+    }
+}
+
 /// Write iterator returned by [`SyncBtreeMap::iter_mut`].
 pub struct BtreeMapIterMut<'a, K, V> {
     _w: WriteLock<'a>,
@@ -119,6 +135,181 @@ impl<'a, K, V> Iterator for BtreeMapIterMut<'a, K, V> {
     }
 }
 
+/// Read guard returned by the ordered lookups of [`SyncBtreeMap`]
+/// (`first_key_value`, `floor`, ...).
+pub type BtreeMapKeyValue<'a, K, V> = ReadEntryGuard<'a, K, V>;
+
+/// Read iterator returned by [`SyncBtreeMap::range`].
+pub struct BtreeMapRange<'a, K, V> {
+    _r: ReadLock<'a>,
+    inner: MapRange<'a, K, V>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, K, V> Iterator for BtreeMapRange<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for BtreeMapRange<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+/// Write iterator returned by [`SyncBtreeMap::range_mut`].
+pub struct BtreeMapRangeMut<'a, K, V> {
+    _w: WriteLock<'a>,
+    inner: MapRangeMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for BtreeMapRangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for BtreeMapRangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+/// A view into a single entry of a [`SyncBtreeMap`], returned by
+/// [`SyncBtreeMap::entry`]; mirrors [`std::collections::btree_map::Entry`].
+///
+/// It holds the writer lock, so the lookup and the change it leads to are one
+/// atomic step; the guards it turns into keep the lock until dropped.
+pub enum BtreeMapEntry<'a, K, V> {
+    Occupied(BtreeMapOccupiedEntry<'a, K, V>),
+    Vacant(BtreeMapVacantEntry<'a, K, V>),
+}
+
+/// An occupied entry of a [`SyncBtreeMap`], see [`BtreeMapEntry`].
+pub struct BtreeMapOccupiedEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: btree_map::OccupiedEntry<'a, K, V>,
+}
+
+/// A vacant entry of a [`SyncBtreeMap`], see [`BtreeMapEntry`].
+pub struct BtreeMapVacantEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: btree_map::VacantEntry<'a, K, V>,
+}
+
+impl<'a, K: Ord, V> BtreeMapEntry<'a, K, V> {
+    #[inline]
+    pub(crate) fn new(w: WriteLock<'a>, entry: btree_map::Entry<'a, K, V>) -> Self {
+        match entry {
+            btree_map::Entry::Occupied(inner) => {
+                BtreeMapEntry::Occupied(BtreeMapOccupiedEntry { w, inner })
+            }
+            btree_map::Entry::Vacant(inner) => {
+                BtreeMapEntry::Vacant(BtreeMapVacantEntry { w, inner })
+            }
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            BtreeMapEntry::Occupied(e) => e.key(),
+            BtreeMapEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> BtreeMapRefMut<'a, K, V> {
+        match self {
+            BtreeMapEntry::Occupied(e) => e.into_mut(),
+            BtreeMapEntry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> BtreeMapRefMut<'a, K, V> {
+        match self {
+            BtreeMapEntry::Occupied(e) => e.into_mut(),
+            BtreeMapEntry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> BtreeMapRefMut<'a, K, V> {
+        match self {
+            BtreeMapEntry::Occupied(e) => e.into_mut(),
+            BtreeMapEntry::Vacant(e) => {
+                let v = default(e.key());
+                e.insert(v)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> BtreeMapRefMut<'a, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            BtreeMapEntry::Occupied(mut e) => {
+                f(e.get_mut());
+                BtreeMapEntry::Occupied(e)
+            }
+            BtreeMapEntry::Vacant(e) => BtreeMapEntry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Ord, V> BtreeMapOccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn get(&self) -> &V {
+        self.inner.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.inner.get_mut()
+    }
+
+    /// Turns the entry into a write guard to the value.
+    pub fn into_mut(self) -> BtreeMapRefMut<'a, K, V> {
+        BtreeMapRefMut::new(WriteGuard::new(self.w, self.inner.into_mut()))
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        self.inner.insert(value)
+    }
+
+    pub fn remove(self) -> V {
+        self.inner.remove()
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.inner.remove_entry()
+    }
+}
+
+impl<'a, K: Ord, V> BtreeMapVacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn into_key(self) -> K {
+        self.inner.into_key()
+    }
+
+    /// Inserts the value and returns a write guard to it.
+    pub fn insert(self, value: V) -> BtreeMapRefMut<'a, K, V> {
+        BtreeMapRefMut::new(WriteGuard::new(self.w, self.inner.insert(value)))
+    }
+}
+
 /// this sync map used to many reader,writer less.space-for-time strategy
 ///
 /// Reads are lock-free: `get`/`iter`/`dirty_ref`/`len`/`contains_key` only
@@ -133,17 +324,16 @@ impl<'a, K, V> Iterator for BtreeMapIterMut<'a, K, V> {
 /// method while a read/write guard is alive in the same scope: drop the guard
 /// first (e.g. `drop(g)` before `insert`/`remove`/`get_mut`), otherwise the
 /// writer waits for its own guard and deadlocks.
+/// Debug builds detect this and panic with a message naming the container;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy) to configure it.
 pub struct SyncBtreeMap<K: Eq + Hash, V> {
-    dirty: UnsafeCell<BTreeMap<K, V>>,
-    write: Mutex<()>,
-    id: usize,
-    writing: AtomicBool,
-    registry: Mutex<Vec<std::boxed::Box<AtomicUsize>>>,
+    dirty: SyncCell<CopyOnWrite<BTreeMap<K, V>>>,
 }
 
-// SAFETY: all writers hold `write` and wait for `readers` to drain before
-// touching `dirty`; readers either see a consistent snapshot or retry while a
-// writer is active, so concurrent access to `dirty` is race-free.
+// SAFETY: `dirty` is a `SyncCell`: all writers hold its writer lock and wait
+// for readers to drain before touching the data; readers either see a
+// consistent snapshot or retry while a writer is active, so concurrent access
+// to `dirty` is race-free.
 unsafe impl<K: Eq + Hash, V: Send> Send for SyncBtreeMap<K, V> {}
 unsafe impl<K: Eq + Hash, V: Sync> Sync for SyncBtreeMap<K, V> {}
 
@@ -151,49 +341,13 @@ impl<K, V> SyncBtreeMap<K, V>
 where
     K: Eq + Hash,
 {
-    #[inline]
-    fn begin_read(&self) -> &AtomicUsize {
-        // The counter lives in thread-local storage: concurrent readers only
-        // touch their own cache line and never contend with each other. SeqCst
-        // closes the store-buffering window with the writer's all-zero scan.
-        let count = super::reader_count_for(self.id, &self.registry);
-        loop {
-            count.fetch_add(1, Ordering::SeqCst);
-            if !self.writing.load(Ordering::SeqCst) {
-                return count;
-            }
-            count.fetch_sub(1, Ordering::SeqCst);
-            std::thread::yield_now();
-        }
-    }
-
-    #[inline]
-    fn begin_write(&self) -> WriteLock<'_> {
-        let lock = self.write.lock();
-        self.writing.store(true, Ordering::SeqCst);
-        loop {
-            let registry = self.registry.lock();
-            let all_zero = registry.iter().all(|c| c.load(Ordering::SeqCst) == 0);
-            if all_zero {
-                break;
-            }
-            drop(registry);
-            std::thread::yield_now();
-        }
-        WriteLock::new(lock, &self.writing)
-    }
-
     pub fn new_arc() -> Arc<Self> {
         Arc::new(Self::new())
     }
 
     pub fn new() -> Self {
         Self {
-            dirty: UnsafeCell::new(BTreeMap::new()),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(CopyOnWrite::new(BTreeMap::new()), "SyncBtreeMap"),
         }
     }
 
@@ -206,11 +360,7 @@ where
         K: Ord,
     {
         Self {
-            dirty: UnsafeCell::new(map),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(CopyOnWrite::new(map), "SyncBtreeMap"),
         }
     }
 
@@ -218,53 +368,127 @@ where
     where
         K: Ord,
     {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.insert(k, v)
     }
 
     pub fn insert_mut(&mut self, k: K, v: V) -> Option<V>
     where
         K: Ord,
     {
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        self.dirty.get_mut().get_mut().insert(k, v)
     }
 
     pub fn remove(&self, k: &K) -> Option<V>
     where
         K: Ord,
     {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.remove(k)
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.remove(k)
     }
 
     pub fn remove_mut(&mut self, k: &K) -> Option<V>
     where
         K: Ord,
     {
-        unsafe { &mut *self.dirty.get() }.remove(k)
+        self.dirty.get_mut().get_mut().remove(k)
     }
 
     pub fn len(&self) -> usize {
-        let count = self.begin_read();
-        let n = unsafe { &*self.dirty.get() }.len();
-        count.fetch_sub(1, Ordering::Release);
-        n
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.is_empty();
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.is_empty()
     }
 
     pub fn clear(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.clear();
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.clear();
     }
 
     pub fn clear_mut(&mut self) {
-        unsafe { &mut *self.dirty.get() }.clear();
+        self.dirty.get_mut().get_mut().clear();
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, under one writer
+    /// lock.
+    pub fn retain<F>(&self, f: F)
+    where
+        K: Ord,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.retain(f);
+    }
+
+    /// Removes every entry and returns them. The map is swapped out under the
+    /// writer lock, so the lock is released before the entries are iterated.
+    pub fn drain(&self) -> MapIntoIter<K, V>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write();
+        std::mem::take(unsafe { self.map_mut() }).into_iter()
+    }
+
+    /// Inserts every entry of `iter` under one writer lock.
+    pub fn extend<I>(&self, iter: I)
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.extend(iter);
+    }
+
+    /// Like [`SyncBtreeMap::extend`], but returns what [`SyncBtreeMap::insert`] would
+    /// have returned for each entry, in order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        iter.into_iter().map(|(k, v)| m.insert(k, v)).collect()
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty. Both
+    /// maps stay write-locked for the whole move, so readers find each entry
+    /// in one of them; they are locked in address order, so two opposite
+    /// `append`s cannot deadlock.
+    pub fn append(&self, other: &Self)
+    where
+        K: Ord,
+    {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let (first, second) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let _first = first.dirty.begin_cow_write();
+        let _second = second.dirty.begin_cow_write();
+        let (m, o) = unsafe { (self.map_mut(), other.map_mut()) };
+        m.append(o);
+    }
+
+    /// Removes the given keys under one writer lock and returns the removed
+    /// values; missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        keys.into_iter().filter_map(|k| m.remove(k)).collect()
     }
 
     pub fn shrink_to_fit(&self) {}
@@ -297,22 +521,138 @@ where
     /// assert_eq!(map.get(&2).is_none(), true);
     /// ```
     #[inline]
-    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<BtreeMapGet<'_, V>>
+    pub fn get<Q>(&self, k: &Q) -> Option<BtreeMapGet<'_, V>>
     where
         K: Borrow<Q> + Ord,
-        Q: Ord,
-    {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        match m.get(k) {
-            Some(v) => Some(ReadGuard::new(count, v)),
-            None => {
-                count.fetch_sub(1, Ordering::Release);
+        Q: Ord + ?Sized,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        m.get(k).map(|v| ReadGuard::new(r, v))
+    }
+
+    /// `Send` version of [`SyncBtreeMap::get`]: the guard can be held across an
+    /// `.await` in a multi-threaded runtime and dropped on any thread.
+    pub fn get_send<Q>(&self, k: &Q) -> Option<SendReadGuard<'_, V>>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        m.get(k).map(|v| SendReadGuard::new(r, v))
+    }
+
+    /// Gets the entry of the key for in-place insert-or-modify, holding the
+    /// writer lock until the entry (or the guard it turns into) is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncBtreeMap;
+    ///
+    /// let m = SyncBtreeMap::new();
+    /// *m.entry("a").or_insert(0) += 1;
+    /// m.entry("a").and_modify(|v| *v += 1).or_insert(0);
+    /// assert_eq!(*m.get("a").unwrap(), 2);
+    /// ```
+    pub fn entry(&self, k: K) -> BtreeMapEntry<'_, K, V>
+    where
+        K: Ord,
+    {
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        BtreeMapEntry::new(w, m.entry(k))
+    }
+
+    /// Returns a read guard to the value of the key, inserting `f()` first if
+    /// the key is missing. The writer lock is only taken (and `f` only run)
+    /// when the key is missing; the write lock is then downgraded, so no other
+    /// writer can remove the new value before the guard is returned.
+    pub fn get_or_insert_with<F>(&self, k: K, f: F) -> BtreeMapGet<'_, V>
+    where
+        K: Ord,
+        F: FnOnce() -> V,
+    {
+        if let Some(g) = self.get(&k) {
+            return g;
+        }
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        let v: &V = m.entry(k).or_insert_with(f);
+        let v = v as *const V;
+        let r = w.downgrade();
+        ReadGuard::new(r, unsafe { &*v })
+    }
+
+    /// Sets the value of the key to `f(current value)` under the writer lock:
+    /// `Some` inserts or replaces, `None` removes. Returns the previous value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncBtreeMap;
+    ///
+    /// let m = SyncBtreeMap::new();
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// assert_eq!(*m.get(&1).unwrap(), 2);
+    /// m.compute(1, |_| None);
+    /// assert!(m.is_empty());
+    /// ```
+    pub fn compute<F>(&self, k: K, f: F) -> Option<V>
+    where
+        K: Ord,
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        match m.entry(k) {
+            btree_map::Entry::Occupied(mut e) => match f(Some(e.get())) {
+                Some(v) => Some(e.insert(v)),
+                None => Some(e.remove()),
+            },
+            btree_map::Entry::Vacant(e) => {
+                if let Some(v) = f(None) {
+                    e.insert(v);
+                }
                 None
             }
         }
     }
 
+    /// Runs `f` on the value of the key under the writer lock. Returns `None`
+    /// (without running `f`) if the key is missing.
+    pub fn update<Q, R, F>(&self, k: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.get_mut(k).map(f)
+    }
+
+    /// Replaces the value of the key with `new` if it equals `expected`.
+    /// Returns the previous value, or gives `new` back as `Err` if the key is
+    /// missing or holds another value. A mismatch seen by a read returns
+    /// without taking the writer lock.
+    pub fn compare_and_swap<Q>(&self, k: &Q, expected: &V, new: V) -> Result<V, V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        V: PartialEq,
+    {
+        if !self.get(k).is_some_and(|v| *v == *expected) {
+            return Err(new);
+        }
+        let _w = self.dirty.begin_cow_write();
+        match unsafe { self.map_mut() }.get_mut(k) {
+            Some(v) if *v == *expected => Ok(std::mem::replace(v, new)),
+            _ => Err(new),
+        }
+    }
+
     /// Returns a write-guarded mutable reference to the value of the key.
     ///
     /// The guard holds the writer lock (writers are mutually exclusive and
@@ -324,63 +664,485 @@ where
     where
         K: Ord,
     {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Some(BtreeMapRefMut::new(WriteGuard::new(w, v))),
+            None => None,
+        }
+    }
+
+    /// Async version of [`SyncBtreeMap::insert`]: awaits the writer lock and
+    /// the reader drain instead of blocking the OS thread.
+    pub async fn insert_async(&self, k: K, v: V) -> Option<V>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write_async().await;
+        unsafe { self.map_mut() }.insert(k, v)
+    }
+
+    /// Async version of [`SyncBtreeMap::remove`].
+    pub async fn remove_async(&self, k: &K) -> Option<V>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write_async().await;
+        unsafe { self.map_mut() }.remove(k)
+    }
+
+    /// Async version of [`SyncBtreeMap::get_mut`].
+    pub async fn get_mut_async(&self, k: &K) -> Option<BtreeMapRefMut<'_, K, V>>
+    where
+        K: Ord,
+    {
+        let w = self.dirty.begin_cow_write_async().await;
+        let m = unsafe { self.map_mut() };
         match m.get_mut(k) {
             Some(v) => Some(BtreeMapRefMut::new(WriteGuard::new(w, v))),
             None => None,
         }
     }
 
+    /// Returns an upgradable read guard to the whole map, for read-then-write
+    /// logic that must not race with other writers:
+    ///
+    /// ```
+    /// use dark_std::sync::SyncBtreeMap;
+    ///
+    /// let m = SyncBtreeMap::<i32, i32>::new();
+    /// let g = m.upgradable_read();
+    /// if !g.contains_key(&1) {
+    ///     g.upgrade().insert(1, 1);
+    /// }
+    /// assert_eq!(*m.get(&1).unwrap(), 1);
+    /// ```
+    ///
+    /// At most one upgradable guard exists at a time; plain readers keep
+    /// reading alongside it, other writers wait. See [`UpgradableReadGuard`].
+    pub fn upgradable_read(&self) -> UpgradableReadGuard<'_, BTreeMap<K, V>> {
+        let u = self.dirty.begin_upgradable();
+        // Writes go through `unshare`, never through this pointer.
+        let m = unsafe { self.map() } as *const BTreeMap<K, V> as *mut BTreeMap<K, V>;
+        UpgradableReadGuard::new(u, m).with_unshare(self as *const Self as *const (), Self::unshare)
+    }
+
+    /// Runs `f` with exclusive access to the whole map: one writer lock and
+    /// one reader drain for the whole batch, and readers see all of its
+    /// changes or none of them.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        K: Ord,
+        F: FnOnce(&mut BTreeMap<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_cow_write();
+        f(unsafe { self.map_mut() })
+    }
+
+    /// Like [`SyncBtreeMap::write`], but every change made through the
+    /// [`Transaction`] is rolled back if `f` returns `Err` or panics.
+    pub fn transact<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        K: Ord + Clone,
+        V: Clone,
+        F: FnOnce(&mut Transaction<'_, BTreeMap<K, V>>) -> Result<R, Error>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let mut tx = Transaction::new(unsafe { self.map_mut() });
+        let r = f(&mut tx)?;
+        tx.commit();
+        Ok(r)
+    }
+
+    /// Runs `f` with exclusive access to the whole map, awaiting the writer
+    /// lock and the reader drain instead of blocking the OS thread.
+    pub async fn write_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut BTreeMap<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_cow_write_async().await;
+        f(unsafe { self.map_mut() })
+    }
+
+    /// Runs `f` with shared access to the whole map, awaiting the end of an
+    /// active write instead of yielding the OS thread.
+    pub async fn read_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&BTreeMap<K, V>) -> R,
+    {
+        let _r = self.dirty.begin_read_async().await;
+        f(unsafe { self.map() })
+    }
+
+    /// Bounded version of [`SyncBtreeMap::insert`]: returns a `Timeout` error if
+    /// the write cannot start within `timeout` (e.g. a leaked `ReadGuard`
+    /// keeps readers from draining). Blocked readers resume either way.
+    pub fn insert_timeout(&self, k: K, v: V, timeout: Duration) -> Result<Option<V>, Error>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write_timeout(timeout)?;
+        Ok(unsafe { self.map_mut() }.insert(k, v))
+    }
+
+    /// Bounded version of [`SyncBtreeMap::remove`].
+    pub fn remove_timeout(&self, k: &K, timeout: Duration) -> Result<Option<V>, Error>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write_timeout(timeout)?;
+        Ok(unsafe { self.map_mut() }.remove(k))
+    }
+
+    /// Bounded version of [`SyncBtreeMap::get_mut`].
+    pub fn get_mut_timeout(
+        &self,
+        k: &K,
+        timeout: Duration,
+    ) -> Result<Option<BtreeMapRefMut<'_, K, V>>, Error>
+    where
+        K: Ord,
+    {
+        let w = self.dirty.begin_cow_write_timeout(timeout)?;
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(BtreeMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the default write timeout, see
+    /// [`SyncBtreeMap::set_default_write_timeout`].
+    pub fn default_write_timeout(&self) -> Option<Duration> {
+        self.dirty.default_write_timeout()
+    }
+
+    /// Makes the `try_*` writes wait up to `timeout` for the writer lock and
+    /// the reader drain, then return a `Timeout` error, instead of failing
+    /// at once with `WouldBlock`. Blocking writes (`insert`, `remove`,
+    /// `get_mut`...) are not affected and still wait forever. `None` (the
+    /// default) turns it off.
+    pub fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.dirty.set_default_write_timeout(timeout)
+    }
+
+    /// Contention counters of this map, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    /// Non-blocking version of [`SyncBtreeMap::insert`]: returns a `WouldBlock`
+    /// error instead of waiting when another writer holds the lock or readers
+    /// are active, so the caller can fall back, queue the write or retry.
+    pub fn try_insert(&self, k: K, v: V) -> Result<Option<V>, Error>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.try_begin_cow_write()?;
+        Ok(unsafe { self.map_mut() }.insert(k, v))
+    }
+
+    /// Non-blocking version of [`SyncBtreeMap::remove`].
+    pub fn try_remove(&self, k: &K) -> Result<Option<V>, Error>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.try_begin_cow_write()?;
+        Ok(unsafe { self.map_mut() }.remove(k))
+    }
+
+    /// Non-blocking version of [`SyncBtreeMap::get_mut`].
+    pub fn try_get_mut(&self, k: &K) -> Result<Option<BtreeMapRefMut<'_, K, V>>, Error>
+    where
+        K: Ord,
+    {
+        let w = self.dirty.try_begin_cow_write()?;
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(BtreeMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Non-blocking version of [`SyncBtreeMap::clear`].
+    pub fn try_clear(&self) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_cow_write()?;
+        unsafe { self.map_mut() }.clear();
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncBtreeMap::iter_mut`].
+    pub fn try_iter_mut(&self) -> Result<BtreeMapIterMut<'_, K, V>, Error> {
+        let w = self.dirty.try_begin_cow_write()?;
+        let m = unsafe { self.map_mut() };
+        Ok(BtreeMapIterMut {
+            _w: w,
+            inner: m.iter_mut(),
+        })
+    }
+
     #[inline]
-    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         K: Borrow<Q> + Ord,
-        Q: Ord,
+        Q: Ord + ?Sized,
     {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.contains_key(k);
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.contains_key(k)
     }
 
     pub fn iter(&self) -> BtreeMapIter<'_, K, V> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
         BtreeMapIter {
-            count,
+            _r: r,
             inner: m.iter(),
             _not_send: PhantomData,
         }
     }
 
     pub fn iter_mut(&self) -> BtreeMapIterMut<'_, K, V> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
         BtreeMapIterMut {
             _w: w,
             inner: m.iter_mut(),
         }
     }
 
-    pub fn into_iter(self) -> MapIntoIter<K, V>
+    /// Iterates the entries whose keys are in `range`, in key order, under a
+    /// read lock held until the iterator is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncBtreeMap;
+    ///
+    /// let jobs = SyncBtreeMap::new();
+    /// for t in [10, 20, 30, 40] {
+    ///     jobs.insert(t, format!("job@{}", t));
+    /// }
+    /// let due: Vec<_> = jobs.range(..=25).map(|(t, _)| *t).collect();
+    /// assert_eq!(due, [10, 20]);
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> BtreeMapRange<'_, K, V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        BtreeMapRange {
+            _r: r,
+            inner: m.range(range),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Like [`SyncBtreeMap::range`], but yields mutable values under the
+    /// writer lock.
+    pub fn range_mut<Q, R>(&self, range: R) -> BtreeMapRangeMut<'_, K, V>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        BtreeMapRangeMut {
+            _w: w,
+            inner: m.range_mut(range),
+        }
+    }
+
+    /// The entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<BtreeMapKeyValue<'_, K, V>>
     where
         K: Ord,
     {
-        self.into_inner().into_iter()
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        m.first_key_value().map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    /// The entry with the largest key.
+    pub fn last_key_value(&self) -> Option<BtreeMapKeyValue<'_, K, V>>
+    where
+        K: Ord,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        m.last_key_value().map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.pop_first()
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.pop_last()
+    }
+
+    /// The entry with the largest key less than or equal to `k`.
+    pub fn floor<Q>(&self, k: &Q) -> Option<BtreeMapKeyValue<'_, K, V>>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.bound((Bound::Unbounded, Bound::Included(k)), true)
+    }
+
+    /// The entry with the smallest key greater than or equal to `k`.
+    pub fn ceiling<Q>(&self, k: &Q) -> Option<BtreeMapKeyValue<'_, K, V>>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.bound((Bound::Included(k), Bound::Unbounded), false)
+    }
+
+    /// The last (`last == true`) or first entry of `range`.
+    fn bound<Q>(
+        &self,
+        range: (Bound<&Q>, Bound<&Q>),
+        last: bool,
+    ) -> Option<BtreeMapKeyValue<'_, K, V>>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let r = self.dirty.begin_read();
+        let mut range = unsafe { self.map() }.range::<Q, _>(range);
+        let kv = if last {
+            range.next_back()
+        } else {
+            range.next()
+        };
+        kv.map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    /// Moves the entries with keys greater than or equal to `k` into a new
+    /// map, under one writer lock.
+    pub fn split_off<Q>(&self, k: &Q) -> Self
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let _w = self.dirty.begin_cow_write();
+        Self::with_map(unsafe { self.map_mut() }.split_off(k))
     }
 
     pub fn dirty_ref(&self) -> ReadMapGuard<'_, BTreeMap<K, V>> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        ReadMapGuard::new(count, m)
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        ReadMapGuard::new(r, m)
+    }
+
+    /// `Send` version of [`SyncBtreeMap::dirty_ref`], e.g. to iterate the whole
+    /// container across `.await` points.
+    pub fn dirty_ref_send(&self) -> SendReadMapGuard<'_, BTreeMap<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        SendReadMapGuard::new(r, m)
+    }
+
+    /// Owned version of [`SyncBtreeMap::get`] on an `Arc`-wrapped map: the guard
+    /// keeps the map alive instead of borrowing it, so it can be stored,
+    /// returned or moved into a spawned task.
+    pub fn get_owned<Q>(self: &Arc<Self>, k: &Q) -> Option<BtreeMapGetOwned<K, V>>
+    where
+        K: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        let v = m.get(k)?;
+        Some(unsafe { OwnedReadGuard::new(self.clone(), r, v) })
+    }
+
+    /// Owned version of [`SyncBtreeMap::iter`]: yields clones of the entries while
+    /// holding a read lock, see [`OwnedIter`].
+    pub fn iter_owned(self: &Arc<Self>) -> BtreeMapIterOwned<K, V>
+    where
+        K: 'static,
+        V: 'static,
+    {
+        let r = self.dirty.begin_read_send();
+        let m: &'static BTreeMap<K, V> = unsafe { &*(self.map() as *const _) };
+        unsafe { OwnedIter::new(self.clone(), r, m.iter()) }
+    }
+
+    /// Owned version of [`SyncBtreeMap::dirty_ref`].
+    pub fn read_owned(self: &Arc<Self>) -> OwnedReadGuard<Self, BTreeMap<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        unsafe { OwnedReadGuard::new(self.clone(), r, m) }
+    }
+
+    /// Returns an immutable view of the map as it is now, in O(1).
+    ///
+    /// The view is an `Arc` to the map's data, so it can be kept, sent to
+    /// another thread or serialized at leisure without holding a reader slot:
+    /// writers are not held back by it. The first write after a snapshot
+    /// copies the map (once) if the snapshot is still alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncBtreeMap;
+    ///
+    /// let m = SyncBtreeMap::new();
+    /// m.insert(1, 1);
+    /// let snap = m.snapshot();
+    /// m.insert(1, 2);
+    /// assert_eq!(snap[&1], 1);
+    /// assert_eq!(*m.get(&1).unwrap(), 2);
+    /// ```
+    pub fn snapshot(&self) -> Arc<BTreeMap<K, V>>
+    where
+        K: Ord + Clone + Sync,
+        V: Clone + Sync,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.snapshot()
     }
 
     pub fn into_inner(self) -> BTreeMap<K, V>
     where
         K: Ord,
     {
-        self.dirty.into_inner()
+        self.dirty.into_inner().into_inner()
+    }
+
+    /// The map. The caller must hold a read or write lock.
+    #[inline]
+    unsafe fn map(&self) -> &BTreeMap<K, V> {
+        (*self.dirty.data_ptr()).get()
+    }
+
+    /// The map, copied out of any live snapshot first. The caller must hold
+    /// the write lock.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn map_mut(&self) -> &mut BTreeMap<K, V> {
+        (*self.dirty.data_ptr()).get_mut()
+    }
+
+    /// [`SyncBtreeMap::map_mut`] of the map at `this`, for upgradable reads.
+    unsafe fn unshare(this: *const ()) -> *mut BTreeMap<K, V> {
+        (*(this as *const Self)).map_mut()
     }
 }
 
@@ -389,7 +1151,7 @@ impl<K: Eq + Hash + Ord, V> IntoIterator for SyncBtreeMap<K, V> {
     type IntoIter = MapIntoIter<K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        self.into_inner().into_iter()
     }
 }
 
@@ -414,7 +1176,7 @@ where
     type Output = V;
 
     fn index(&self, index: &K) -> &Self::Output {
-        unsafe { &(&*self.dirty.get())[index] }
+        unsafe { &self.map()[index] }
     }
 }
 
@@ -483,3 +1245,57 @@ impl<K: Eq + Hash, V> Default for SyncBtreeMap<K, V> {
         SyncBtreeMap::new()
     }
 }
+
+/// Changes made inside [`SyncBtreeMap::transact`]. Each one records how to undo
+/// itself; reads go through `Deref` to the map.
+impl<'a, K, V> Transaction<'a, BTreeMap<K, V>>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let old = self.data().insert(k.clone(), v);
+        match old.clone() {
+            Some(o) => self.on_rollback(move |m| {
+                m.insert(k, o);
+            }),
+            None => self.on_rollback(move |m| {
+                m.remove(&k);
+            }),
+        }
+        old
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (k, v) = self.data().remove_entry(k)?;
+        let old = v.clone();
+        self.on_rollback(move |m| {
+            m.insert(k, old);
+        });
+        Some(v)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (key, v) = self.data().get_key_value(k)?;
+        let (key, old) = (key.clone(), v.clone());
+        self.on_rollback(move |m| {
+            if let Some(v) = m.get_mut::<K>(&key) {
+                *v = old;
+            }
+        });
+        self.data().get_mut(k)
+    }
+
+    pub fn clear(&mut self) {
+        let old = std::mem::take(self.data());
+        self.on_rollback(move |m| *m = old);
+    }
+}
@@ -1,19 +1,26 @@
-use parking_lot::Mutex;
 use serde::{Deserializer, Serialize, Serializer};
 use std::borrow::Borrow;
-use std::cell::UnsafeCell;
 use std::collections::{
-    hash_map::IntoIter as MapIntoIter, hash_map::Iter as MapIter,
+    hash_map, hash_map::IntoIter as MapIntoIter, hash_map::Iter as MapIter,
     hash_map::IterMut as MapIterMut, HashMap as Map,
 };
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut, Index};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use super::{ReadGuard, ReadMapGuard, WriteGuard, WriteLock};
+use crate::errors::Error;
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::cow::CopyOnWrite;
+use super::{
+    OwnedIter, OwnedReadGuard, ReadGuard, ReadLock, ReadMapGuard, SendReadGuard, SendReadMapGuard,
+    SyncCell, Transaction, UpgradableReadGuard, WriteGuard, WriteLock,
+};
 
 /// Read guard returned by [`SyncHashMap::get`].
 pub type HashMapGet<'a, V> = ReadGuard<'a, V>;
@@ -72,17 +79,11 @@ impl<'a, K, V: Eq> Eq for HashMapRefMut<'a, K, V> {}
 
 /// Read iterator returned by [`SyncHashMap::iter`].
 pub struct HashMapIter<'a, K, V> {
-    count: &'a AtomicUsize,
+    _r: ReadLock<'a>,
     inner: MapIter<'a, K, V>,
     _not_send: PhantomData<*const ()>,
 }
 
-impl<'a, K, V> Drop for HashMapIter<'a, K, V> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
-    }
-}
-
 impl<'a, K, V> Iterator for HashMapIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
@@ -91,6 +92,20 @@ impl<'a, K, V> Iterator for HashMapIter<'a, K, V> {
     }
 }
 
+/// Owned read guard returned by [`SyncHashMap::get_owned`].
+pub type HashMapGetOwned<K, V> = OwnedReadGuard<SyncHashMap<K, V>, V>;
+
+/// Owned iterator returned by [`SyncHashMap::iter_owned`].
+pub type HashMapIterOwned<K, V> = OwnedIter<SyncHashMap<K, V>, MapIter<'static, K, V>>;
+
+impl<K: Eq + Hash + Clone, V: Clone> Iterator for HashMapIterOwned<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner().next().map(|(k, v)| (k.clone(), v.clone()))
+    }
+}
+
 /// Write iterator returned by [`SyncHashMap::iter_mut`].
 pub struct HashMapIterMut<'a, K, V> {
     _w: WriteLock<'a>,
@@ -119,6 +134,134 @@ impl<'a, K, V> Iterator for HashMapIterMut<'a, K, V> {
     }
 }
 
+/// A view into a single entry of a [`SyncHashMap`], returned by
+/// [`SyncHashMap::entry`]; mirrors [`std::collections::hash_map::Entry`].
+///
+/// It holds the writer lock, so the lookup and the change it leads to are one
+/// atomic step; the guards it turns into keep the lock until dropped.
+pub enum HashMapEntry<'a, K, V> {
+    Occupied(HashMapOccupiedEntry<'a, K, V>),
+    Vacant(HashMapVacantEntry<'a, K, V>),
+}
+
+/// An occupied entry of a [`SyncHashMap`], see [`HashMapEntry`].
+pub struct HashMapOccupiedEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: hash_map::OccupiedEntry<'a, K, V>,
+}
+
+/// A vacant entry of a [`SyncHashMap`], see [`HashMapEntry`].
+pub struct HashMapVacantEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: hash_map::VacantEntry<'a, K, V>,
+}
+
+impl<'a, K, V> HashMapEntry<'a, K, V> {
+    #[inline]
+    pub(crate) fn new(w: WriteLock<'a>, entry: hash_map::Entry<'a, K, V>) -> Self {
+        match entry {
+            hash_map::Entry::Occupied(inner) => {
+                HashMapEntry::Occupied(HashMapOccupiedEntry { w, inner })
+            }
+            hash_map::Entry::Vacant(inner) => HashMapEntry::Vacant(HashMapVacantEntry { w, inner }),
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            HashMapEntry::Occupied(e) => e.key(),
+            HashMapEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> HashMapRefMut<'a, K, V> {
+        match self {
+            HashMapEntry::Occupied(e) => e.into_mut(),
+            HashMapEntry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> HashMapRefMut<'a, K, V> {
+        match self {
+            HashMapEntry::Occupied(e) => e.into_mut(),
+            HashMapEntry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> HashMapRefMut<'a, K, V> {
+        match self {
+            HashMapEntry::Occupied(e) => e.into_mut(),
+            HashMapEntry::Vacant(e) => {
+                let v = default(e.key());
+                e.insert(v)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> HashMapRefMut<'a, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            HashMapEntry::Occupied(mut e) => {
+                f(e.get_mut());
+                HashMapEntry::Occupied(e)
+            }
+            HashMapEntry::Vacant(e) => HashMapEntry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K, V> HashMapOccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn get(&self) -> &V {
+        self.inner.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.inner.get_mut()
+    }
+
+    /// Turns the entry into a write guard to the value.
+    pub fn into_mut(self) -> HashMapRefMut<'a, K, V> {
+        HashMapRefMut::new(WriteGuard::new(self.w, self.inner.into_mut()))
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        self.inner.insert(value)
+    }
+
+    pub fn remove(self) -> V {
+        self.inner.remove()
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.inner.remove_entry()
+    }
+}
+
+impl<'a, K, V> HashMapVacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn into_key(self) -> K {
+        self.inner.into_key()
+    }
+
+    /// Inserts the value and returns a write guard to it.
+    pub fn insert(self, value: V) -> HashMapRefMut<'a, K, V> {
+        HashMapRefMut::new(WriteGuard::new(self.w, self.inner.insert(value)))
+    }
+}
+
 /// this sync map used to many reader,writer less.space-for-time strategy
 ///
 /// Reads are lock-free: `get`/`iter`/`dirty_ref`/`len`/`contains_key` only
@@ -133,17 +276,16 @@ impl<'a, K, V> Iterator for HashMapIterMut<'a, K, V> {
 /// method while a read/write guard is alive in the same scope: drop the guard
 /// first (e.g. `drop(g)` before `insert`/`remove`/`get_mut`), otherwise the
 /// writer waits for its own guard and deadlocks.
+/// Debug builds detect this and panic with a message naming the container;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy) to configure it.
 pub struct SyncHashMap<K: Eq + Hash, V> {
-    dirty: UnsafeCell<Map<K, V>>,
-    write: Mutex<()>,
-    id: usize,
-    writing: AtomicBool,
-    registry: Mutex<Vec<std::boxed::Box<AtomicUsize>>>,
+    dirty: SyncCell<CopyOnWrite<Map<K, V>>>,
 }
 
-// SAFETY: all writers hold `write` and wait for `readers` to drain before
-// touching `dirty`; readers either see a consistent snapshot or retry while a
-// writer is active, so concurrent access to `dirty` is race-free.
+// SAFETY: `dirty` is a `SyncCell`: all writers hold its writer lock and wait
+// for readers to drain before touching the data; readers either see a
+// consistent snapshot or retry while a writer is active, so concurrent access
+// to `dirty` is race-free.
 unsafe impl<K: Eq + Hash, V: Send> Send for SyncHashMap<K, V> {}
 unsafe impl<K: Eq + Hash, V: Sync> Sync for SyncHashMap<K, V> {}
 
@@ -151,120 +293,151 @@ impl<K, V> SyncHashMap<K, V>
 where
     K: Eq + Hash,
 {
-    #[inline]
-    fn begin_read(&self) -> &AtomicUsize {
-        // The counter lives in thread-local storage: concurrent readers only
-        // touch their own cache line and never contend with each other. SeqCst
-        // closes the store-buffering window with the writer's all-zero scan.
-        let count = super::reader_count_for(self.id, &self.registry);
-        loop {
-            count.fetch_add(1, Ordering::SeqCst);
-            if !self.writing.load(Ordering::SeqCst) {
-                return count;
-            }
-            count.fetch_sub(1, Ordering::SeqCst);
-            std::thread::yield_now();
-        }
-    }
-
-    #[inline]
-    fn begin_write(&self) -> WriteLock<'_> {
-        let lock = self.write.lock();
-        self.writing.store(true, Ordering::SeqCst);
-        loop {
-            let registry = self.registry.lock();
-            let all_zero = registry.iter().all(|c| c.load(Ordering::SeqCst) == 0);
-            if all_zero {
-                break;
-            }
-            drop(registry);
-            std::thread::yield_now();
-        }
-        WriteLock::new(lock, &self.writing)
-    }
-
     pub fn new_arc() -> Arc<Self> {
         Arc::new(Self::new())
     }
 
     pub fn new() -> Self {
         Self {
-            dirty: UnsafeCell::new(Map::new()),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(CopyOnWrite::new(Map::new()), "SyncHashMap"),
+        }
+    }
+
+    /// An empty map whose deadlock diagnostics name the container `name`.
+    pub(crate) fn named(name: &'static str) -> Self {
+        Self {
+            dirty: SyncCell::named(CopyOnWrite::new(Map::new()), name),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            dirty: UnsafeCell::new(Map::with_capacity(capacity)),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(
+                CopyOnWrite::new(Map::with_capacity(capacity)),
+                "SyncHashMap",
+            ),
         }
     }
 
     pub fn with_map(map: Map<K, V>) -> Self {
         Self {
-            dirty: UnsafeCell::new(map),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(CopyOnWrite::new(map), "SyncHashMap"),
         }
     }
 
     pub fn insert(&self, k: K, v: V) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.insert(k, v)
     }
 
     pub fn insert_mut(&mut self, k: K, v: V) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        self.dirty.get_mut().get_mut().insert(k, v)
     }
 
     pub fn remove(&self, k: &K) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.remove(k)
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.remove(k)
     }
 
     pub fn remove_mut(&mut self, k: &K) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.remove(k)
+        self.dirty.get_mut().get_mut().remove(k)
     }
 
     pub fn len(&self) -> usize {
-        let count = self.begin_read();
-        let n = unsafe { &*self.dirty.get() }.len();
-        count.fetch_sub(1, Ordering::Release);
-        n
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.is_empty();
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.is_empty()
     }
 
     pub fn clear(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.clear();
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.clear();
     }
 
     pub fn clear_mut(&mut self) {
-        unsafe { &mut *self.dirty.get() }.clear();
+        self.dirty.get_mut().get_mut().clear();
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, under one writer
+    /// lock.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.retain(f);
+    }
+
+    /// Removes every entry and returns them. The map is swapped out under the
+    /// writer lock, so the lock is released before the entries are iterated.
+    pub fn drain(&self) -> MapIntoIter<K, V> {
+        let _w = self.dirty.begin_cow_write();
+        std::mem::take(unsafe { self.map_mut() }).into_iter()
+    }
+
+    /// Inserts every entry of `iter` under one writer lock.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.extend(iter);
+    }
+
+    /// Like [`SyncHashMap::extend`], but returns what [`SyncHashMap::insert`] would
+    /// have returned for each entry, in order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        iter.into_iter().map(|(k, v)| m.insert(k, v)).collect()
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty. Both
+    /// maps stay write-locked for the whole move, so readers find each entry
+    /// in one of them; they are locked in address order, so two opposite
+    /// `append`s cannot deadlock.
+    pub fn append(&self, other: &Self) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let (first, second) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let _first = first.dirty.begin_cow_write();
+        let _second = second.dirty.begin_cow_write();
+        let (m, o) = unsafe { (self.map_mut(), other.map_mut()) };
+        m.extend(o.drain());
+    }
+
+    /// Removes the given keys under one writer lock and returns the removed
+    /// values; missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        keys.into_iter().filter_map(|k| m.remove(k)).collect()
     }
 
     pub fn shrink_to_fit(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.shrink_to_fit();
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.shrink_to_fit();
     }
 
     pub fn shrink_to_fit_mut(&mut self) {
-        unsafe { &mut *self.dirty.get() }.shrink_to_fit()
+        self.dirty.get_mut().get_mut().shrink_to_fit()
     }
 
     pub fn from(map: Map<K, V>) -> Self
@@ -295,22 +468,133 @@ where
     /// assert_eq!(map.get(&2).is_none(), true);
     /// ```
     #[inline]
-    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<HashMapGet<'_, V>>
+    pub fn get<Q>(&self, k: &Q) -> Option<HashMapGet<'_, V>>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        m.get(k).map(|v| ReadGuard::new(r, v))
+    }
+
+    /// `Send` version of [`SyncHashMap::get`]: the guard can be held across an
+    /// `.await` in a multi-threaded runtime and dropped on any thread.
+    pub fn get_send<Q>(&self, k: &Q) -> Option<SendReadGuard<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        m.get(k).map(|v| SendReadGuard::new(r, v))
+    }
+
+    /// Gets the entry of the key for in-place insert-or-modify, holding the
+    /// writer lock until the entry (or the guard it turns into) is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncHashMap;
+    ///
+    /// let m = SyncHashMap::new();
+    /// *m.entry("a").or_insert(0) += 1;
+    /// m.entry("a").and_modify(|v| *v += 1).or_insert(0);
+    /// assert_eq!(*m.get("a").unwrap(), 2);
+    /// ```
+    pub fn entry(&self, k: K) -> HashMapEntry<'_, K, V> {
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        HashMapEntry::new(w, m.entry(k))
+    }
+
+    /// Returns a read guard to the value of the key, inserting `f()` first if
+    /// the key is missing. The writer lock is only taken (and `f` only run)
+    /// when the key is missing; the write lock is then downgraded, so no other
+    /// writer can remove the new value before the guard is returned.
+    pub fn get_or_insert_with<F>(&self, k: K, f: F) -> HashMapGet<'_, V>
+    where
+        F: FnOnce() -> V,
+    {
+        if let Some(g) = self.get(&k) {
+            return g;
+        }
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        let v: &V = m.entry(k).or_insert_with(f);
+        let v = v as *const V;
+        let r = w.downgrade();
+        ReadGuard::new(r, unsafe { &*v })
+    }
+
+    /// Sets the value of the key to `f(current value)` under the writer lock:
+    /// `Some` inserts or replaces, `None` removes. Returns the previous value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncHashMap;
+    ///
+    /// let m = SyncHashMap::new();
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// assert_eq!(*m.get(&1).unwrap(), 2);
+    /// m.compute(1, |_| None);
+    /// assert!(m.is_empty());
+    /// ```
+    pub fn compute<F>(&self, k: K, f: F) -> Option<V>
+    where
+        F: FnOnce(Option<&V>) -> Option<V>,
     {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        match m.get(k) {
-            Some(v) => Some(ReadGuard::new(count, v)),
-            None => {
-                count.fetch_sub(1, Ordering::Release);
+        let _w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
+        match m.entry(k) {
+            hash_map::Entry::Occupied(mut e) => match f(Some(e.get())) {
+                Some(v) => Some(e.insert(v)),
+                None => Some(e.remove()),
+            },
+            hash_map::Entry::Vacant(e) => {
+                if let Some(v) = f(None) {
+                    e.insert(v);
+                }
                 None
             }
         }
     }
 
+    /// Runs `f` on the value of the key under the writer lock. Returns `None`
+    /// (without running `f`) if the key is missing.
+    pub fn update<Q, R, F>(&self, k: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let _w = self.dirty.begin_cow_write();
+        unsafe { self.map_mut() }.get_mut(k).map(f)
+    }
+
+    /// Replaces the value of the key with `new` if it equals `expected`.
+    /// Returns the previous value, or gives `new` back as `Err` if the key is
+    /// missing or holds another value. A mismatch seen by a read returns
+    /// without taking the writer lock.
+    pub fn compare_and_swap<Q>(&self, k: &Q, expected: &V, new: V) -> Result<V, V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: PartialEq,
+    {
+        if !self.get(k).is_some_and(|v| *v == *expected) {
+            return Err(new);
+        }
+        let _w = self.dirty.begin_cow_write();
+        match unsafe { self.map_mut() }.get_mut(k) {
+            Some(v) if *v == *expected => Ok(std::mem::replace(v, new)),
+            _ => Err(new),
+        }
+    }
+
     /// Returns a write-guarded mutable reference to the value of the key.
     ///
     /// The guard holds the writer lock (writers are mutually exclusive and
@@ -319,57 +603,321 @@ where
     /// before calling another method from the same scope.
     #[inline]
     pub fn get_mut(&self, k: &K) -> Option<HashMapRefMut<'_, K, V>> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
         match m.get_mut(k) {
             Some(v) => Some(HashMapRefMut::new(WriteGuard::new(w, v))),
             None => None,
         }
     }
 
+    /// Async version of [`SyncHashMap::insert`]: awaits the writer lock and the
+    /// reader drain instead of blocking the OS thread.
+    pub async fn insert_async(&self, k: K, v: V) -> Option<V> {
+        let _w = self.dirty.begin_cow_write_async().await;
+        unsafe { self.map_mut() }.insert(k, v)
+    }
+
+    /// Async version of [`SyncHashMap::remove`].
+    pub async fn remove_async(&self, k: &K) -> Option<V> {
+        let _w = self.dirty.begin_cow_write_async().await;
+        unsafe { self.map_mut() }.remove(k)
+    }
+
+    /// Async version of [`SyncHashMap::get_mut`].
+    pub async fn get_mut_async(&self, k: &K) -> Option<HashMapRefMut<'_, K, V>> {
+        let w = self.dirty.begin_cow_write_async().await;
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Some(HashMapRefMut::new(WriteGuard::new(w, v))),
+            None => None,
+        }
+    }
+
+    /// Returns an upgradable read guard to the whole map, for read-then-write
+    /// logic that must not race with other writers:
+    ///
+    /// ```
+    /// use dark_std::sync::SyncHashMap;
+    ///
+    /// let m = SyncHashMap::<i32, i32>::new();
+    /// let g = m.upgradable_read();
+    /// if !g.contains_key(&1) {
+    ///     g.upgrade().insert(1, 1);
+    /// }
+    /// assert_eq!(*m.get(&1).unwrap(), 1);
+    /// ```
+    ///
+    /// At most one upgradable guard exists at a time; plain readers keep
+    /// reading alongside it, other writers wait. See [`UpgradableReadGuard`].
+    pub fn upgradable_read(&self) -> UpgradableReadGuard<'_, Map<K, V>> {
+        let u = self.dirty.begin_upgradable();
+        // Writes go through `unshare`, never through this pointer.
+        let m = unsafe { self.map() } as *const Map<K, V> as *mut Map<K, V>;
+        UpgradableReadGuard::new(u, m).with_unshare(self as *const Self as *const (), Self::unshare)
+    }
+
+    /// Runs `f` with exclusive access to the whole map: one writer lock and
+    /// one reader drain for the whole batch, and readers see all of its
+    /// changes or none of them.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Map<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_cow_write();
+        f(unsafe { self.map_mut() })
+    }
+
+    /// Like [`SyncHashMap::write`], but every change made through the
+    /// [`Transaction`] is rolled back if `f` returns `Err` or panics.
+    pub fn transact<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        K: Clone,
+        V: Clone,
+        F: FnOnce(&mut Transaction<'_, Map<K, V>>) -> Result<R, Error>,
+    {
+        let _w = self.dirty.begin_cow_write();
+        let mut tx = Transaction::new(unsafe { self.map_mut() });
+        let r = f(&mut tx)?;
+        tx.commit();
+        Ok(r)
+    }
+
+    /// Runs `f` with exclusive access to the whole map, awaiting the writer
+    /// lock and the reader drain instead of blocking the OS thread.
+    pub async fn write_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Map<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_cow_write_async().await;
+        f(unsafe { self.map_mut() })
+    }
+
+    /// Runs `f` with shared access to the whole map, awaiting the end of an
+    /// active write instead of yielding the OS thread.
+    pub async fn read_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Map<K, V>) -> R,
+    {
+        let _r = self.dirty.begin_read_async().await;
+        f(unsafe { self.map() })
+    }
+
+    /// Bounded version of [`SyncHashMap::insert`]: returns a `Timeout` error if
+    /// the write cannot start within `timeout` (e.g. a leaked `ReadGuard`
+    /// keeps readers from draining). Blocked readers resume either way.
+    pub fn insert_timeout(&self, k: K, v: V, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_cow_write_timeout(timeout)?;
+        Ok(unsafe { self.map_mut() }.insert(k, v))
+    }
+
+    /// Bounded version of [`SyncHashMap::remove`].
+    pub fn remove_timeout(&self, k: &K, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_cow_write_timeout(timeout)?;
+        Ok(unsafe { self.map_mut() }.remove(k))
+    }
+
+    /// Bounded version of [`SyncHashMap::get_mut`].
+    pub fn get_mut_timeout(
+        &self,
+        k: &K,
+        timeout: Duration,
+    ) -> Result<Option<HashMapRefMut<'_, K, V>>, Error> {
+        let w = self.dirty.begin_cow_write_timeout(timeout)?;
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(HashMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the default write timeout, see
+    /// [`SyncHashMap::set_default_write_timeout`].
+    pub fn default_write_timeout(&self) -> Option<Duration> {
+        self.dirty.default_write_timeout()
+    }
+
+    /// Makes the `try_*` writes wait up to `timeout` for the writer lock and
+    /// the reader drain, then return a `Timeout` error, instead of failing
+    /// at once with `WouldBlock`. Blocking writes (`insert`, `remove`,
+    /// `get_mut`...) are not affected and still wait forever. `None` (the
+    /// default) turns it off.
+    pub fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.dirty.set_default_write_timeout(timeout)
+    }
+
+    /// Contention counters of this map, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    /// Non-blocking version of [`SyncHashMap::insert`]: returns a `WouldBlock`
+    /// error instead of waiting when another writer holds the lock or readers
+    /// are active, so the caller can fall back, queue the write or retry.
+    pub fn try_insert(&self, k: K, v: V) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_cow_write()?;
+        Ok(unsafe { self.map_mut() }.insert(k, v))
+    }
+
+    /// Non-blocking version of [`SyncHashMap::remove`].
+    pub fn try_remove(&self, k: &K) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_cow_write()?;
+        Ok(unsafe { self.map_mut() }.remove(k))
+    }
+
+    /// Non-blocking version of [`SyncHashMap::get_mut`].
+    pub fn try_get_mut(&self, k: &K) -> Result<Option<HashMapRefMut<'_, K, V>>, Error> {
+        let w = self.dirty.try_begin_cow_write()?;
+        let m = unsafe { self.map_mut() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(HashMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Non-blocking version of [`SyncHashMap::clear`].
+    pub fn try_clear(&self) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_cow_write()?;
+        unsafe { self.map_mut() }.clear();
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncHashMap::iter_mut`].
+    pub fn try_iter_mut(&self) -> Result<HashMapIterMut<'_, K, V>, Error> {
+        let w = self.dirty.try_begin_cow_write()?;
+        let m = unsafe { self.map_mut() };
+        Ok(HashMapIterMut {
+            _w: w,
+            inner: m.iter_mut(),
+        })
+    }
+
     #[inline]
-    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.contains_key(k);
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { self.map() }.contains_key(k)
     }
 
     pub fn iter(&self) -> HashMapIter<'_, K, V> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
         HashMapIter {
-            count,
+            _r: r,
             inner: m.iter(),
             _not_send: PhantomData,
         }
     }
 
     pub fn iter_mut(&self) -> HashMapIterMut<'_, K, V> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_cow_write();
+        let m = unsafe { self.map_mut() };
         HashMapIterMut {
             _w: w,
             inner: m.iter_mut(),
         }
     }
 
-    pub fn into_iter(self) -> MapIntoIter<K, V> {
-        self.into_inner().into_iter()
+    pub fn dirty_ref(&self) -> ReadMapGuard<'_, Map<K, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { self.map() };
+        ReadMapGuard::new(r, m)
     }
 
-    pub fn dirty_ref(&self) -> ReadMapGuard<'_, Map<K, V>> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        ReadMapGuard::new(count, m)
+    /// `Send` version of [`SyncHashMap::dirty_ref`], e.g. to iterate the whole
+    /// container across `.await` points.
+    pub fn dirty_ref_send(&self) -> SendReadMapGuard<'_, Map<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        SendReadMapGuard::new(r, m)
+    }
+
+    /// Owned version of [`SyncHashMap::get`] on an `Arc`-wrapped map: the guard
+    /// keeps the map alive instead of borrowing it, so it can be stored,
+    /// returned or moved into a spawned task.
+    pub fn get_owned<Q>(self: &Arc<Self>, k: &Q) -> Option<HashMapGetOwned<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        let v = m.get(k)?;
+        Some(unsafe { OwnedReadGuard::new(self.clone(), r, v) })
+    }
+
+    /// Owned version of [`SyncHashMap::iter`]: yields clones of the entries while
+    /// holding a read lock, see [`OwnedIter`].
+    pub fn iter_owned(self: &Arc<Self>) -> HashMapIterOwned<K, V>
+    where
+        K: 'static,
+        V: 'static,
+    {
+        let r = self.dirty.begin_read_send();
+        let m: &'static Map<K, V> = unsafe { &*(self.map() as *const _) };
+        unsafe { OwnedIter::new(self.clone(), r, m.iter()) }
+    }
+
+    /// Owned version of [`SyncHashMap::dirty_ref`].
+    pub fn read_owned(self: &Arc<Self>) -> OwnedReadGuard<Self, Map<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { self.map() };
+        unsafe { OwnedReadGuard::new(self.clone(), r, m) }
+    }
+
+    /// Returns an immutable view of the map as it is now, in O(1).
+    ///
+    /// The view is an `Arc` to the map's data, so it can be kept, sent to
+    /// another thread or serialized at leisure without holding a reader slot:
+    /// writers are not held back by it. The first write after a snapshot
+    /// copies the map (once) if the snapshot is still alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncHashMap;
+    ///
+    /// let m = SyncHashMap::new();
+    /// m.insert(1, 1);
+    /// let snap = m.snapshot();
+    /// m.insert(1, 2);
+    /// assert_eq!(snap[&1], 1);
+    /// assert_eq!(*m.get(&1).unwrap(), 2);
+    /// ```
+    pub fn snapshot(&self) -> Arc<Map<K, V>>
+    where
+        K: Clone + Sync,
+        V: Clone + Sync,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.snapshot()
     }
 
     pub fn into_inner(self) -> Map<K, V> {
-        self.dirty.into_inner()
+        self.dirty.into_inner().into_inner()
+    }
+
+    /// The map. The caller must hold a read or write lock.
+    #[inline]
+    unsafe fn map(&self) -> &Map<K, V> {
+        (*self.dirty.data_ptr()).get()
+    }
+
+    /// The map, copied out of any live snapshot first. The caller must hold
+    /// the write lock.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn map_mut(&self) -> &mut Map<K, V> {
+        (*self.dirty.data_ptr()).get_mut()
+    }
+
+    /// [`SyncHashMap::map_mut`] of the map at `this`, for upgradable reads.
+    unsafe fn unshare(this: *const ()) -> *mut Map<K, V> {
+        (*(this as *const Self)).map_mut()
     }
 }
 
@@ -381,7 +929,7 @@ where
     type IntoIter = MapIntoIter<K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        self.into_inner().into_iter()
     }
 }
 
@@ -406,7 +954,7 @@ where
     type Output = V;
 
     fn index(&self, index: &K) -> &Self::Output {
-        unsafe { &(&*self.dirty.get())[index] }
+        unsafe { &self.map()[index] }
     }
 }
 
@@ -475,3 +1023,57 @@ impl<K: Eq + Hash, V> Default for SyncHashMap<K, V> {
         SyncHashMap::new()
     }
 }
+
+/// Changes made inside [`SyncHashMap::transact`]. Each one records how to undo
+/// itself; reads go through `Deref` to the map.
+impl<'a, K, V> Transaction<'a, Map<K, V>>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let old = self.data().insert(k.clone(), v);
+        match old.clone() {
+            Some(o) => self.on_rollback(move |m| {
+                m.insert(k, o);
+            }),
+            None => self.on_rollback(move |m| {
+                m.remove(&k);
+            }),
+        }
+        old
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (k, v) = self.data().remove_entry(k)?;
+        let old = v.clone();
+        self.on_rollback(move |m| {
+            m.insert(k, old);
+        });
+        Some(v)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, v) = self.data().get_key_value(k)?;
+        let (key, old) = (key.clone(), v.clone());
+        self.on_rollback(move |m| {
+            if let Some(v) = m.get_mut::<K>(&key) {
+                *v = old;
+            }
+        });
+        self.data().get_mut(k)
+    }
+
+    pub fn clear(&mut self) {
+        let old = std::mem::take(self.data());
+        self.on_rollback(move |m| *m = old);
+    }
+}
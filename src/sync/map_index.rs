@@ -1,22 +1,33 @@
 use indexmap::map::{
     IndexMap as Map, IntoIter as MapIntoIter, Iter as MapIter, IterMut as MapIterMut,
 };
-use parking_lot::Mutex;
 use serde::{Deserializer, Serialize, Serializer};
 use std::borrow::Borrow;
-use std::cell::UnsafeCell;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut, Index};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use super::{ReadGuard, ReadMapGuard, WriteGuard, WriteLock};
+use crate::errors::Error;
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::{
+    OwnedIter, OwnedReadGuard, ReadEntryGuard, ReadGuard, ReadLock, ReadMapGuard, SendReadGuard,
+    SendReadMapGuard, SyncCell, Transaction, UpgradableReadGuard, WriteGuard, WriteLock,
+};
 
 /// Read guard returned by [`SyncIndexMap::get`].
 pub type IndexMapGet<'a, V> = ReadGuard<'a, V>;
 
+/// Read guard returned by the positional lookups of [`SyncIndexMap`]
+/// (`get_index`, `first`, ...).
+pub type IndexMapKeyValue<'a, K, V> = ReadEntryGuard<'a, K, V>;
+
 /// Write guard returned by [`SyncIndexMap::get_mut`].
 pub struct IndexMapRefMut<'a, K, V> {
     inner: WriteGuard<'a, V>,
@@ -75,17 +86,11 @@ pub type HashMapRefMut<'a, K, V> = IndexMapRefMut<'a, K, V>;
 
 /// Read iterator returned by [`SyncIndexMap::iter`].
 pub struct IndexMapIter<'a, K, V> {
-    count: &'a AtomicUsize,
+    _r: ReadLock<'a>,
     inner: MapIter<'a, K, V>,
     _not_send: PhantomData<*const ()>,
 }
 
-impl<'a, K, V> Drop for IndexMapIter<'a, K, V> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
-    }
-}
-
 impl<'a, K, V> Iterator for IndexMapIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
@@ -94,6 +99,20 @@ impl<'a, K, V> Iterator for IndexMapIter<'a, K, V> {
     }
 }
 
+/// Owned read guard returned by [`SyncIndexMap::get_owned`].
+pub type IndexMapGetOwned<K, V> = OwnedReadGuard<SyncIndexMap<K, V>, V>;
+
+/// Owned iterator returned by [`SyncIndexMap::iter_owned`].
+pub type IndexMapIterOwned<K, V> = OwnedIter<SyncIndexMap<K, V>, MapIter<'static, K, V>>;
+
+impl<K: Eq + Hash + Clone, V: Clone> Iterator for IndexMapIterOwned<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner().next().map(|(k, v)| (k.clone(), v.clone()))
+    }
+}
+
 /// Write iterator returned by [`SyncIndexMap::iter_mut`].
 pub struct IndexMapIterMut<'a, K, V> {
     _w: WriteLock<'a>,
@@ -122,6 +141,167 @@ impl<'a, K, V> Iterator for IndexMapIterMut<'a, K, V> {
     }
 }
 
+/// A view into a single entry of a [`SyncIndexMap`], returned by
+/// [`SyncIndexMap::entry`]; mirrors [`indexmap::map::Entry`].
+///
+/// It holds the writer lock, so the lookup and the change it leads to are one
+/// atomic step; the guards it turns into keep the lock until dropped.
+pub enum IndexMapEntry<'a, K, V> {
+    Occupied(IndexMapOccupiedEntry<'a, K, V>),
+    Vacant(IndexMapVacantEntry<'a, K, V>),
+}
+
+/// An occupied entry of a [`SyncIndexMap`], see [`IndexMapEntry`].
+pub struct IndexMapOccupiedEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: indexmap::map::OccupiedEntry<'a, K, V>,
+}
+
+/// A vacant entry of a [`SyncIndexMap`], see [`IndexMapEntry`].
+pub struct IndexMapVacantEntry<'a, K, V> {
+    w: WriteLock<'a>,
+    inner: indexmap::map::VacantEntry<'a, K, V>,
+}
+
+impl<'a, K: Eq + Hash, V> IndexMapEntry<'a, K, V> {
+    #[inline]
+    pub(crate) fn new(w: WriteLock<'a>, entry: indexmap::map::Entry<'a, K, V>) -> Self {
+        match entry {
+            indexmap::map::Entry::Occupied(inner) => {
+                IndexMapEntry::Occupied(IndexMapOccupiedEntry { w, inner })
+            }
+            indexmap::map::Entry::Vacant(inner) => {
+                IndexMapEntry::Vacant(IndexMapVacantEntry { w, inner })
+            }
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            IndexMapEntry::Occupied(e) => e.key(),
+            IndexMapEntry::Vacant(e) => e.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> IndexMapRefMut<'a, K, V> {
+        match self {
+            IndexMapEntry::Occupied(e) => e.into_mut(),
+            IndexMapEntry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> IndexMapRefMut<'a, K, V> {
+        match self {
+            IndexMapEntry::Occupied(e) => e.into_mut(),
+            IndexMapEntry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> IndexMapRefMut<'a, K, V> {
+        match self {
+            IndexMapEntry::Occupied(e) => e.into_mut(),
+            IndexMapEntry::Vacant(e) => {
+                let v = default(e.key());
+                e.insert(v)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> IndexMapRefMut<'a, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            IndexMapEntry::Occupied(mut e) => {
+                f(e.get_mut());
+                IndexMapEntry::Occupied(e)
+            }
+            IndexMapEntry::Vacant(e) => IndexMapEntry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash, V> IndexMapOccupiedEntry<'a, K, V> {
+    /// The position of the entry in the map.
+    pub fn index(&self) -> usize {
+        self.inner.index()
+    }
+
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn get(&self) -> &V {
+        self.inner.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.inner.get_mut()
+    }
+
+    /// Turns the entry into a write guard to the value.
+    pub fn into_mut(self) -> IndexMapRefMut<'a, K, V> {
+        IndexMapRefMut::new(WriteGuard::new(self.w, self.inner.into_mut()))
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        self.inner.insert(value)
+    }
+
+    /// Removes the entry like [`SyncIndexMap::remove`], by swapping the last
+    /// entry into its place.
+    pub fn remove(self) -> V {
+        self.inner.swap_remove()
+    }
+
+    /// See [`IndexMapOccupiedEntry::remove`].
+    pub fn remove_entry(self) -> (K, V) {
+        self.inner.swap_remove_entry()
+    }
+
+    pub fn swap_remove(self) -> V {
+        self.inner.swap_remove()
+    }
+
+    pub fn swap_remove_entry(self) -> (K, V) {
+        self.inner.swap_remove_entry()
+    }
+
+    /// Removes the entry and shifts the following ones down, keeping their
+    /// order. O(n).
+    pub fn shift_remove(self) -> V {
+        self.inner.shift_remove()
+    }
+
+    pub fn shift_remove_entry(self) -> (K, V) {
+        self.inner.shift_remove_entry()
+    }
+}
+
+impl<'a, K: Eq + Hash, V> IndexMapVacantEntry<'a, K, V> {
+    /// The position the entry will get when inserted.
+    pub fn index(&self) -> usize {
+        self.inner.index()
+    }
+
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn into_key(self) -> K {
+        self.inner.into_key()
+    }
+
+    /// Inserts the value and returns a write guard to it.
+    pub fn insert(self, value: V) -> IndexMapRefMut<'a, K, V> {
+        IndexMapRefMut::new(WriteGuard::new(self.w, self.inner.insert(value)))
+    }
+}
+
 /// this sync map used to many reader,writer less.space-for-time strategy
 ///
 /// Reads are lock-free: `get`/`iter`/`dirty_ref`/`len`/`contains_key` only
@@ -136,17 +316,16 @@ impl<'a, K, V> Iterator for IndexMapIterMut<'a, K, V> {
 /// method while a read/write guard is alive in the same scope: drop the guard
 /// first (e.g. `drop(g)` before `insert`/`remove`/`get_mut`), otherwise the
 /// writer waits for its own guard and deadlocks.
+/// Debug builds detect this and panic with a message naming the container;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy) to configure it.
 pub struct SyncIndexMap<K: Eq + Hash, V> {
-    dirty: UnsafeCell<Map<K, V>>,
-    write: Mutex<()>,
-    id: usize,
-    writing: AtomicBool,
-    registry: Mutex<Vec<std::boxed::Box<AtomicUsize>>>,
+    dirty: SyncCell<Map<K, V>>,
 }
 
-// SAFETY: all writers hold `write` and wait for `readers` to drain before
-// touching `dirty`; readers either see a consistent snapshot or retry while a
-// writer is active, so concurrent access to `dirty` is race-free.
+// SAFETY: `dirty` is a `SyncCell`: all writers hold its writer lock and wait
+// for readers to drain before touching the data; readers either see a
+// consistent snapshot or retry while a writer is active, so concurrent access
+// to `dirty` is race-free.
 unsafe impl<K: Eq + Hash, V: Send> Send for SyncIndexMap<K, V> {}
 unsafe impl<K: Eq + Hash, V: Sync> Sync for SyncIndexMap<K, V> {}
 
@@ -154,120 +333,141 @@ impl<K, V> SyncIndexMap<K, V>
 where
     K: Eq + Hash,
 {
-    #[inline]
-    fn begin_read(&self) -> &AtomicUsize {
-        // The counter lives in thread-local storage: concurrent readers only
-        // touch their own cache line and never contend with each other. SeqCst
-        // closes the store-buffering window with the writer's all-zero scan.
-        let count = super::reader_count_for(self.id, &self.registry);
-        loop {
-            count.fetch_add(1, Ordering::SeqCst);
-            if !self.writing.load(Ordering::SeqCst) {
-                return count;
-            }
-            count.fetch_sub(1, Ordering::SeqCst);
-            std::thread::yield_now();
-        }
-    }
-
-    #[inline]
-    fn begin_write(&self) -> WriteLock<'_> {
-        let lock = self.write.lock();
-        self.writing.store(true, Ordering::SeqCst);
-        loop {
-            let registry = self.registry.lock();
-            let all_zero = registry.iter().all(|c| c.load(Ordering::SeqCst) == 0);
-            if all_zero {
-                break;
-            }
-            drop(registry);
-            std::thread::yield_now();
-        }
-        WriteLock::new(lock, &self.writing)
-    }
-
     pub fn new_arc() -> Arc<Self> {
         Arc::new(Self::new())
     }
 
     pub fn new() -> Self {
         Self {
-            dirty: UnsafeCell::new(Map::new()),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(Map::new(), "SyncIndexMap"),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            dirty: UnsafeCell::new(Map::with_capacity(capacity)),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(Map::with_capacity(capacity), "SyncIndexMap"),
         }
     }
 
     pub fn with_map(map: Map<K, V>) -> Self {
         Self {
-            dirty: UnsafeCell::new(map),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(map, "SyncIndexMap"),
         }
     }
 
     pub fn insert(&self, k: K, v: V) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.insert(k, v)
     }
 
     pub fn insert_mut(&mut self, k: K, v: V) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.insert(k, v)
+        self.dirty.get_mut().insert(k, v)
     }
 
     pub fn remove(&self, k: &K) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.swap_remove(k)
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.swap_remove(k)
     }
 
     pub fn remove_mut(&mut self, k: &K) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.swap_remove(k)
+        self.dirty.get_mut().swap_remove(k)
     }
 
     pub fn len(&self) -> usize {
-        let count = self.begin_read();
-        let n = unsafe { &*self.dirty.get() }.len();
-        count.fetch_sub(1, Ordering::Release);
-        n
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.is_empty();
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.is_empty()
     }
 
     pub fn clear(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.clear();
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
     }
 
     pub fn clear_mut(&mut self) {
-        unsafe { &mut *self.dirty.get() }.clear();
+        self.dirty.get_mut().clear();
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, under one writer
+    /// lock.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.retain(f);
+    }
+
+    /// Removes every entry and returns them. The map is swapped out under the
+    /// writer lock, so the lock is released before the entries are iterated.
+    pub fn drain(&self) -> MapIntoIter<K, V> {
+        let _w = self.dirty.begin_write();
+        std::mem::take(unsafe { &mut *self.dirty.data_ptr() }).into_iter()
+    }
+
+    /// Inserts every entry of `iter` under one writer lock.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.extend(iter);
+    }
+
+    /// Like [`SyncIndexMap::extend`], but returns what [`SyncIndexMap::insert`] would
+    /// have returned for each entry, in order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        iter.into_iter().map(|(k, v)| m.insert(k, v)).collect()
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty. Both
+    /// maps stay write-locked for the whole move, so readers find each entry
+    /// in one of them; they are locked in address order, so two opposite
+    /// `append`s cannot deadlock.
+    pub fn append(&self, other: &Self) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let (first, second) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let _first = first.dirty.begin_write();
+        let _second = second.dirty.begin_write();
+        let (m, o) = unsafe { (&mut *self.dirty.data_ptr(), &mut *other.dirty.data_ptr()) };
+        m.extend(o.drain(..));
+    }
+
+    /// Removes the given keys under one writer lock and returns the removed
+    /// values; missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        keys.into_iter().filter_map(|k| m.swap_remove(k)).collect()
     }
 
     pub fn shrink_to_fit(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.shrink_to_fit();
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shrink_to_fit();
     }
 
     pub fn shrink_to_fit_mut(&mut self) {
-        unsafe { &mut *self.dirty.get() }.shrink_to_fit()
+        self.dirty.get_mut().shrink_to_fit()
     }
 
     pub fn from(map: Map<K, V>) -> Self
@@ -298,22 +498,133 @@ where
     /// assert_eq!(map.get(&2).is_none(), true);
     /// ```
     #[inline]
-    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<IndexMapGet<'_, V>>
+    pub fn get<Q>(&self, k: &Q) -> Option<IndexMapGet<'_, V>>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        match m.get(k) {
-            Some(v) => Some(ReadGuard::new(count, v)),
-            None => {
-                count.fetch_sub(1, Ordering::Release);
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(k).map(|v| ReadGuard::new(r, v))
+    }
+
+    /// `Send` version of [`SyncIndexMap::get`]: the guard can be held across an
+    /// `.await` in a multi-threaded runtime and dropped on any thread.
+    pub fn get_send<Q>(&self, k: &Q) -> Option<SendReadGuard<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(k).map(|v| SendReadGuard::new(r, v))
+    }
+
+    /// Gets the entry of the key for in-place insert-or-modify, holding the
+    /// writer lock until the entry (or the guard it turns into) is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncIndexMap;
+    ///
+    /// let m = SyncIndexMap::new();
+    /// *m.entry("a").or_insert(0) += 1;
+    /// m.entry("a").and_modify(|v| *v += 1).or_insert(0);
+    /// assert_eq!(*m.get("a").unwrap(), 2);
+    /// ```
+    pub fn entry(&self, k: K) -> IndexMapEntry<'_, K, V> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        IndexMapEntry::new(w, m.entry(k))
+    }
+
+    /// Returns a read guard to the value of the key, inserting `f()` first if
+    /// the key is missing. The writer lock is only taken (and `f` only run)
+    /// when the key is missing; the write lock is then downgraded, so no other
+    /// writer can remove the new value before the guard is returned.
+    pub fn get_or_insert_with<F>(&self, k: K, f: F) -> IndexMapGet<'_, V>
+    where
+        F: FnOnce() -> V,
+    {
+        if let Some(g) = self.get(&k) {
+            return g;
+        }
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        let v: &V = m.entry(k).or_insert_with(f);
+        let v = v as *const V;
+        let r = w.downgrade();
+        ReadGuard::new(r, unsafe { &*v })
+    }
+
+    /// Sets the value of the key to `f(current value)` under the writer lock:
+    /// `Some` inserts or replaces, `None` removes. Returns the previous value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncIndexMap;
+    ///
+    /// let m = SyncIndexMap::new();
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// m.compute(1, |v| Some(v.map_or(1, |v| v + 1)));
+    /// assert_eq!(*m.get(&1).unwrap(), 2);
+    /// m.compute(1, |_| None);
+    /// assert!(m.is_empty());
+    /// ```
+    pub fn compute<F>(&self, k: K, f: F) -> Option<V>
+    where
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.entry(k) {
+            indexmap::map::Entry::Occupied(mut e) => match f(Some(e.get())) {
+                Some(v) => Some(e.insert(v)),
+                None => Some(e.swap_remove()),
+            },
+            indexmap::map::Entry::Vacant(e) => {
+                if let Some(v) = f(None) {
+                    e.insert(v);
+                }
                 None
             }
         }
     }
 
+    /// Runs `f` on the value of the key under the writer lock. Returns `None`
+    /// (without running `f`) if the key is missing.
+    pub fn update<Q, R, F>(&self, k: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.get_mut(k).map(f)
+    }
+
+    /// Replaces the value of the key with `new` if it equals `expected`.
+    /// Returns the previous value, or gives `new` back as `Err` if the key is
+    /// missing or holds another value. A mismatch seen by a read returns
+    /// without taking the writer lock.
+    pub fn compare_and_swap<Q>(&self, k: &Q, expected: &V, new: V) -> Result<V, V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: PartialEq,
+    {
+        if !self.get(k).is_some_and(|v| *v == *expected) {
+            return Err(new);
+        }
+        let _w = self.dirty.begin_write();
+        match unsafe { &mut *self.dirty.data_ptr() }.get_mut(k) {
+            Some(v) if *v == *expected => Ok(std::mem::replace(v, new)),
+            _ => Err(new),
+        }
+    }
+
     /// Returns a write-guarded mutable reference to the value of the key.
     ///
     /// The guard holds the writer lock (writers are mutually exclusive and
@@ -322,53 +633,426 @@ where
     /// before calling another method from the same scope.
     #[inline]
     pub fn get_mut(&self, k: &K) -> Option<IndexMapRefMut<'_, K, V>> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(k) {
+            Some(v) => Some(IndexMapRefMut::new(WriteGuard::new(w, v))),
+            None => None,
+        }
+    }
+
+    /// Async version of [`SyncIndexMap::insert`]: awaits the writer lock and
+    /// the reader drain instead of blocking the OS thread.
+    pub async fn insert_async(&self, k: K, v: V) -> Option<V> {
+        let _w = self.dirty.begin_write_async().await;
+        unsafe { &mut *self.dirty.data_ptr() }.insert(k, v)
+    }
+
+    /// Async version of [`SyncIndexMap::remove`].
+    pub async fn remove_async(&self, k: &K) -> Option<V> {
+        let _w = self.dirty.begin_write_async().await;
+        unsafe { &mut *self.dirty.data_ptr() }.swap_remove(k)
+    }
+
+    /// Async version of [`SyncIndexMap::get_mut`].
+    pub async fn get_mut_async(&self, k: &K) -> Option<IndexMapRefMut<'_, K, V>> {
+        let w = self.dirty.begin_write_async().await;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
         match m.get_mut(k) {
             Some(v) => Some(IndexMapRefMut::new(WriteGuard::new(w, v))),
             None => None,
         }
     }
 
+    /// Returns an upgradable read guard to the whole map, for read-then-write
+    /// logic that must not race with other writers:
+    ///
+    /// ```
+    /// use dark_std::sync::SyncIndexMap;
+    ///
+    /// let m = SyncIndexMap::<i32, i32>::new();
+    /// let g = m.upgradable_read();
+    /// if !g.contains_key(&1) {
+    ///     g.upgrade().insert(1, 1);
+    /// }
+    /// assert_eq!(*m.get(&1).unwrap(), 1);
+    /// ```
+    ///
+    /// At most one upgradable guard exists at a time; plain readers keep
+    /// reading alongside it, other writers wait. See [`UpgradableReadGuard`].
+    pub fn upgradable_read(&self) -> UpgradableReadGuard<'_, Map<K, V>> {
+        let u = self.dirty.begin_upgradable();
+        UpgradableReadGuard::new(u, self.dirty.data_ptr())
+    }
+
+    /// Runs `f` with exclusive access to the whole map: one writer lock and
+    /// one reader drain for the whole batch, and readers see all of its
+    /// changes or none of them.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Map<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_write();
+        f(unsafe { &mut *self.dirty.data_ptr() })
+    }
+
+    /// Like [`SyncIndexMap::write`], but every change made through the
+    /// [`Transaction`] is rolled back if `f` returns `Err` or panics.
+    pub fn transact<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        K: Clone,
+        V: Clone,
+        F: FnOnce(&mut Transaction<'_, Map<K, V>>) -> Result<R, Error>,
+    {
+        let _w = self.dirty.begin_write();
+        let mut tx = Transaction::new(unsafe { &mut *self.dirty.data_ptr() });
+        let r = f(&mut tx)?;
+        tx.commit();
+        Ok(r)
+    }
+
+    /// Runs `f` with exclusive access to the whole map, awaiting the writer
+    /// lock and the reader drain instead of blocking the OS thread.
+    pub async fn write_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Map<K, V>) -> R,
+    {
+        let _w = self.dirty.begin_write_async().await;
+        f(unsafe { &mut *self.dirty.data_ptr() })
+    }
+
+    /// Runs `f` with shared access to the whole map, awaiting the end of an
+    /// active write instead of yielding the OS thread.
+    pub async fn read_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Map<K, V>) -> R,
+    {
+        let _r = self.dirty.begin_read_async().await;
+        f(unsafe { &*self.dirty.data_ptr() })
+    }
+
+    /// Bounded version of [`SyncIndexMap::insert`]: returns a `Timeout` error if
+    /// the write cannot start within `timeout` (e.g. a leaked `ReadGuard`
+    /// keeps readers from draining). Blocked readers resume either way.
+    pub fn insert_timeout(&self, k: K, v: V, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_write_timeout(timeout)?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.insert(k, v))
+    }
+
+    /// Bounded version of [`SyncIndexMap::remove`].
+    pub fn remove_timeout(&self, k: &K, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_write_timeout(timeout)?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.swap_remove(k))
+    }
+
+    /// Bounded version of [`SyncIndexMap::get_mut`].
+    pub fn get_mut_timeout(
+        &self,
+        k: &K,
+        timeout: Duration,
+    ) -> Result<Option<IndexMapRefMut<'_, K, V>>, Error> {
+        let w = self.dirty.begin_write_timeout(timeout)?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(IndexMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the default write timeout, see
+    /// [`SyncIndexMap::set_default_write_timeout`].
+    pub fn default_write_timeout(&self) -> Option<Duration> {
+        self.dirty.default_write_timeout()
+    }
+
+    /// Makes the `try_*` writes wait up to `timeout` for the writer lock and
+    /// the reader drain, then return a `Timeout` error, instead of failing
+    /// at once with `WouldBlock`. Blocking writes (`insert`, `remove`,
+    /// `get_mut`...) are not affected and still wait forever. `None` (the
+    /// default) turns it off.
+    pub fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.dirty.set_default_write_timeout(timeout)
+    }
+
+    /// Contention counters of this map, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    /// Non-blocking version of [`SyncIndexMap::insert`]: returns a `WouldBlock`
+    /// error instead of waiting when another writer holds the lock or readers
+    /// are active, so the caller can fall back, queue the write or retry.
+    pub fn try_insert(&self, k: K, v: V) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.insert(k, v))
+    }
+
+    /// Non-blocking version of [`SyncIndexMap::remove`].
+    pub fn try_remove(&self, k: &K) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.swap_remove(k))
+    }
+
+    /// Non-blocking version of [`SyncIndexMap::get_mut`].
+    pub fn try_get_mut(&self, k: &K) -> Result<Option<IndexMapRefMut<'_, K, V>>, Error> {
+        let w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(k) {
+            Some(v) => Ok(Some(IndexMapRefMut::new(WriteGuard::new(w, v)))),
+            None => Ok(None),
+        }
+    }
+
+    /// Non-blocking version of [`SyncIndexMap::clear`].
+    pub fn try_clear(&self) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncIndexMap::iter_mut`].
+    pub fn try_iter_mut(&self) -> Result<IndexMapIterMut<'_, K, V>, Error> {
+        let w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        Ok(IndexMapIterMut {
+            _w: w,
+            inner: m.iter_mut(),
+        })
+    }
+
     #[inline]
-    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.contains_key(k);
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.contains_key(k)
     }
 
     pub fn iter(&self) -> IndexMapIter<'_, K, V> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
         IndexMapIter {
-            count,
+            _r: r,
             inner: m.iter(),
             _not_send: PhantomData,
         }
     }
 
     pub fn iter_mut(&self) -> IndexMapIterMut<'_, K, V> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
         IndexMapIterMut {
             _w: w,
             inner: m.iter_mut(),
         }
     }
 
-    pub fn into_iter(self) -> MapIntoIter<K, V> {
-        self.into_inner().into_iter()
+    /// The entry at position `index`.
+    pub fn get_index(&self, index: usize) -> Option<IndexMapKeyValue<'_, K, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get_index(index).map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    /// Write guard to the value at position `index`.
+    pub fn get_index_mut(&self, index: usize) -> Option<IndexMapRefMut<'_, K, V>> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        m.get_index_mut(index)
+            .map(|(_, v)| IndexMapRefMut::new(WriteGuard::new(w, v)))
+    }
+
+    /// The position of the key.
+    pub fn get_index_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.get_index_of(k)
+    }
+
+    /// The position and the entry of the key.
+    pub fn get_full<Q>(&self, k: &Q) -> Option<(usize, IndexMapKeyValue<'_, K, V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get_full(k)
+            .map(|(i, k, v)| (i, ReadEntryGuard::new(r, (k, v))))
+    }
+
+    pub fn first(&self) -> Option<IndexMapKeyValue<'_, K, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.first().map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    pub fn last(&self) -> Option<IndexMapKeyValue<'_, K, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.last().map(|kv| ReadEntryGuard::new(r, kv))
+    }
+
+    /// Inserts the entry at position `index`, shifting the following entries
+    /// up. If the key is already present, its entry is moved to `index` and
+    /// its old value returned.
+    ///
+    /// Panics if `index` is greater than the length (or, for an existing
+    /// key, out of bounds).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncIndexMap;
+    ///
+    /// let chain = SyncIndexMap::new();
+    /// chain.insert("auth", 1);
+    /// chain.insert("handler", 2);
+    /// chain.shift_insert(1, "logging", 3);
+    /// let names: Vec<_> = chain.iter().map(|(k, _)| *k).collect();
+    /// assert_eq!(names, ["auth", "logging", "handler"]);
+    /// ```
+    pub fn shift_insert(&self, index: usize, k: K, v: V) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shift_insert(index, k, v)
+    }
+
+    /// Removes the key by swapping its entry with the last one: O(1), but
+    /// the last entry changes position. Same as [`SyncIndexMap::remove`].
+    pub fn swap_remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.swap_remove(k)
+    }
+
+    /// Removes the key by shifting the following entries down: keeps the
+    /// order, in O(n).
+    pub fn shift_remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shift_remove(k)
+    }
+
+    /// Positional version of [`SyncIndexMap::swap_remove`].
+    pub fn swap_remove_index(&self, index: usize) -> Option<(K, V)> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.swap_remove_index(index)
+    }
+
+    /// Positional version of [`SyncIndexMap::shift_remove`].
+    pub fn shift_remove_index(&self, index: usize) -> Option<(K, V)> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shift_remove_index(index)
+    }
+
+    /// Moves the entry at `from` to `to`, shifting the entries in between.
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn move_index(&self, from: usize, to: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.move_index(from, to)
+    }
+
+    /// Swaps the entries at `a` and `b`.
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn swap_indices(&self, a: usize, b: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.swap_indices(a, b)
+    }
+
+    pub fn sort_keys(&self)
+    where
+        K: Ord,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort_keys()
+    }
+
+    /// Sorts the entries with `cmp`, which gets the key and value of two
+    /// entries. The sort is stable.
+    pub fn sort_by<F>(&self, cmp: F)
+    where
+        F: FnMut(&K, &V, &K, &V) -> Ordering,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort_by(cmp)
+    }
+
+    pub fn reverse(&self) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.reverse()
+    }
+
+    /// Removes and returns the last entry.
+    pub fn pop(&self) -> Option<(K, V)> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop()
+    }
+
+    /// Keeps the first `len` entries and drops the rest.
+    pub fn truncate(&self, len: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.truncate(len)
     }
 
     pub fn dirty_ref(&self) -> ReadMapGuard<'_, Map<K, V>> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        ReadMapGuard::new(count, m)
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        ReadMapGuard::new(r, m)
+    }
+
+    /// `Send` version of [`SyncIndexMap::dirty_ref`], e.g. to iterate the whole
+    /// container across `.await` points.
+    pub fn dirty_ref_send(&self) -> SendReadMapGuard<'_, Map<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        SendReadMapGuard::new(r, m)
+    }
+
+    /// Owned version of [`SyncIndexMap::get`] on an `Arc`-wrapped map: the guard
+    /// keeps the map alive instead of borrowing it, so it can be stored,
+    /// returned or moved into a spawned task.
+    pub fn get_owned<Q>(self: &Arc<Self>, k: &Q) -> Option<IndexMapGetOwned<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        let v = m.get(k)?;
+        Some(unsafe { OwnedReadGuard::new(self.clone(), r, v) })
+    }
+
+    /// Owned version of [`SyncIndexMap::iter`]: yields clones of the entries while
+    /// holding a read lock, see [`OwnedIter`].
+    pub fn iter_owned(self: &Arc<Self>) -> IndexMapIterOwned<K, V>
+    where
+        K: 'static,
+        V: 'static,
+    {
+        let r = self.dirty.begin_read_send();
+        let m: &'static Map<K, V> = unsafe { &*self.dirty.data_ptr() };
+        unsafe { OwnedIter::new(self.clone(), r, m.iter()) }
+    }
+
+    /// Owned version of [`SyncIndexMap::dirty_ref`].
+    pub fn read_owned(self: &Arc<Self>) -> OwnedReadGuard<Self, Map<K, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        unsafe { OwnedReadGuard::new(self.clone(), r, m) }
     }
 
     pub fn into_inner(self) -> Map<K, V> {
@@ -384,7 +1068,7 @@ where
     type IntoIter = MapIntoIter<K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        self.into_inner().into_iter()
     }
 }
 
@@ -409,7 +1093,7 @@ where
     type Output = V;
 
     fn index(&self, index: &K) -> &Self::Output {
-        unsafe { &(&*self.dirty.get())[index] }
+        unsafe { &(&*self.dirty.data_ptr())[index] }
     }
 }
 
@@ -478,3 +1162,62 @@ impl<K: Eq + Hash, V> Default for SyncIndexMap<K, V> {
         SyncIndexMap::new()
     }
 }
+
+/// Changes made inside [`SyncIndexMap::transact`]. Each one records how to
+/// undo itself, order included; reads go through `Deref` to the map.
+impl<'a, K, V> Transaction<'a, Map<K, V>>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let (_, old) = self.data().insert_full(k.clone(), v);
+        match old.clone() {
+            // replacing keeps the entry's position
+            Some(o) => self.on_rollback(move |m| {
+                m.insert(k, o);
+            }),
+            // a new entry is the last one again once later changes are undone
+            None => self.on_rollback(|m| {
+                m.pop();
+            }),
+        }
+        old
+    }
+
+    /// Removes like [`SyncIndexMap::remove`] (`swap_remove`).
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (i, k, v) = self.data().swap_remove_full(k)?;
+        let old = v.clone();
+        self.on_rollback(move |m| {
+            m.insert(k, old);
+            let last = m.len() - 1;
+            m.swap_indices(i, last);
+        });
+        Some(v)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (i, _, v) = self.data().get_full(k)?;
+        let old = v.clone();
+        self.on_rollback(move |m| {
+            if let Some((_, v)) = m.get_index_mut(i) {
+                *v = old;
+            }
+        });
+        self.data().get_mut(k)
+    }
+
+    pub fn clear(&mut self) {
+        let old = std::mem::take(self.data());
+        self.on_rollback(move |m| *m = old);
+    }
+}
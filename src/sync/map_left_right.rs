@@ -0,0 +1,669 @@
+use serde::{Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::collections::{btree_map, hash_map, BTreeMap, HashMap};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::Hash;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::left_right::{Absorb, LeftRight, LeftRightGuard, LeftRightIter};
+
+/// A change recorded in the operation log of a left-right map.
+pub(crate) enum MapOp<K, V> {
+    Insert(K, V),
+    Remove(K),
+    Clear,
+}
+
+impl<K: Eq + Hash, V> Absorb<MapOp<K, V>> for HashMap<K, V> {
+    fn absorb(&mut self, op: MapOp<K, V>) {
+        match op {
+            MapOp::Insert(k, v) => {
+                self.insert(k, v);
+            }
+            MapOp::Remove(k) => {
+                self.remove(&k);
+            }
+            MapOp::Clear => self.clear(),
+        }
+    }
+}
+
+impl<K: Ord, V> Absorb<MapOp<K, V>> for BTreeMap<K, V> {
+    fn absorb(&mut self, op: MapOp<K, V>) {
+        match op {
+            MapOp::Insert(k, v) => {
+                self.insert(k, v);
+            }
+            MapOp::Remove(k) => {
+                self.remove(&k);
+            }
+            MapOp::Clear => self.clear(),
+        }
+    }
+}
+
+/// The changes of one [`LeftRightHashMap::write`] or
+/// [`LeftRightBtreeMap::write`] batch. Reads go through `Deref` to the map
+/// being written, which already contains the earlier changes of the batch.
+pub struct LeftRightBatch<'a, M, K, V> {
+    data: &'a mut M,
+    log: &'a mut Vec<MapOp<K, V>>,
+}
+
+impl<'a, M, K, V> Deref for LeftRightBatch<'a, M, K, V> {
+    type Target = M;
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<'a, K, V> LeftRightBatch<'a, HashMap<K, V>, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let old = self.data.insert(k.clone(), v.clone());
+        self.log.push(MapOp::Insert(k, v));
+        old
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (k, v) = self.data.remove_entry(k)?;
+        self.log.push(MapOp::Remove(k));
+        Some(v)
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.log.push(MapOp::Clear);
+    }
+
+    /// Keeps only the entries for which `f` returns `true`. The values are
+    /// read-only so that the same removals can be replayed on the other copy.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let log = &mut *self.log;
+        self.data.retain(|k, v| {
+            let keep = f(k, v);
+            if !keep {
+                log.push(MapOp::Remove(k.clone()));
+            }
+            keep
+        });
+    }
+
+    /// Removes every entry and returns them.
+    pub fn drain(&mut self) -> HashMap<K, V> {
+        self.log.push(MapOp::Clear);
+        std::mem::take(self.data)
+    }
+}
+
+impl<'a, K, V> LeftRightBatch<'a, BTreeMap<K, V>, K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let old = self.data.insert(k.clone(), v.clone());
+        self.log.push(MapOp::Insert(k, v));
+        old
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (k, v) = self.data.remove_entry(k)?;
+        self.log.push(MapOp::Remove(k));
+        Some(v)
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.log.push(MapOp::Clear);
+    }
+
+    /// Keeps only the entries for which `f` returns `true`. The values are
+    /// read-only so that the same removals can be replayed on the other copy.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let log = &mut *self.log;
+        self.data.retain(|k, v| {
+            let keep = f(k, v);
+            if !keep {
+                log.push(MapOp::Remove(k.clone()));
+            }
+            keep
+        });
+    }
+
+    /// Removes every entry and returns them.
+    pub fn drain(&mut self) -> BTreeMap<K, V> {
+        self.log.push(MapOp::Clear);
+        std::mem::take(self.data)
+    }
+}
+
+/// Read guard returned by [`LeftRightHashMap::get`] and
+/// [`LeftRightBtreeMap::get`].
+pub type LeftRightGet<'a, V> = LeftRightGuard<'a, V>;
+
+/// Read iterator returned by [`LeftRightHashMap::iter`].
+pub type LeftRightHashMapIter<'a, K, V> = LeftRightIter<'a, hash_map::Iter<'a, K, V>>;
+
+/// Read iterator returned by [`LeftRightBtreeMap::iter`].
+pub type LeftRightBtreeMapIter<'a, K, V> = LeftRightIter<'a, btree_map::Iter<'a, K, V>>;
+
+/// A hash map whose readers never wait on writers.
+///
+/// Unlike [`SyncHashMap`](super::SyncHashMap), where a reader that arrives
+/// during a write backs off until the write is done, this map keeps two
+/// copies of the data: readers always read the published copy, while a writer
+/// changes the other one, publishes it, waits for the readers of the old copy
+/// to leave and replays its changes there. Reads are wait-free; writes pay for
+/// doing every change twice, the map takes twice the memory, and `K` and `V`
+/// must be `Clone`. Best for read-mostly data that is reloaded in the
+/// background, such as configuration.
+///
+/// # Deadlock note
+/// A read guard makes the next writer wait until it is dropped: drop the guard
+/// before writing from the same thread. Debug builds detect this and panic;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy).
+pub struct LeftRightHashMap<K, V> {
+    inner: LeftRight<HashMap<K, V>>,
+}
+
+impl<K, V> LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    pub fn new() -> Self {
+        Self::with_map(HashMap::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_map(HashMap::with_capacity(capacity))
+    }
+
+    pub fn with_map(map: HashMap<K, V>) -> Self {
+        Self {
+            inner: LeftRight::new(map, "LeftRightHashMap"),
+        }
+    }
+
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        self.write(|m| m.insert(k, v))
+    }
+
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write(|m| m.remove(k))
+    }
+
+    pub fn clear(&self) {
+        self.write(|m| m.clear())
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, in one batch.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.write(|m| m.retain(f))
+    }
+
+    /// Removes every entry and returns them, in one batch.
+    pub fn drain(&self) -> HashMap<K, V> {
+        self.write(|m| m.drain())
+    }
+
+    /// Inserts every entry of `iter` in one batch.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write(|m| {
+            for (k, v) in iter {
+                m.insert(k, v);
+            }
+        })
+    }
+
+    /// Like [`LeftRightHashMap::extend`], but returns what [`LeftRightHashMap::insert`]
+    /// would have returned for each entry, in order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write(|m| iter.into_iter().map(|(k, v)| m.insert(k, v)).collect())
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty.
+    ///
+    /// The move is not atomic: `other` is drained in one batch and the
+    /// entries are inserted in a second one, so a concurrent reader may find
+    /// an entry in neither map.
+    pub fn append(&self, other: &Self) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let entries = other.drain();
+        self.extend(entries);
+    }
+
+    /// Removes the given keys in one batch and returns the removed values;
+    /// missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        self.write(|m| keys.into_iter().filter_map(|k| m.remove(k)).collect())
+    }
+
+    /// Applies several changes and publishes them together: readers see the
+    /// map before the batch or after it, never in between. If `f` panics, the
+    /// whole batch is dropped and readers keep the map as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::LeftRightHashMap;
+    ///
+    /// let config = LeftRightHashMap::new();
+    /// config.insert("timeout", 10);
+    /// // reload
+    /// config.write(|m| {
+    ///     m.clear();
+    ///     m.insert("timeout", 30);
+    ///     m.insert("retries", 3);
+    /// });
+    /// assert_eq!(*config.get("timeout").unwrap(), 30);
+    /// ```
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut LeftRightBatch<'_, HashMap<K, V>, K, V>) -> R,
+    {
+        self.inner
+            .write(|data, log| f(&mut LeftRightBatch { data, log }))
+    }
+
+    /// Returns a read guard to the value of the key. Never waits, even while
+    /// a write is in progress.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<LeftRightGet<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (r, m) = self.inner.read();
+        m.get(k).map(|v| LeftRightGuard::new(r, v))
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.read().1.contains_key(k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().1.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().1.is_empty()
+    }
+
+    pub fn iter(&self) -> LeftRightHashMapIter<'_, K, V> {
+        let (r, m) = self.inner.read();
+        LeftRightIter::new(r, m.iter())
+    }
+
+    /// A read guard to the whole published map.
+    pub fn read(&self) -> LeftRightGuard<'_, HashMap<K, V>> {
+        let (r, m) = self.inner.read();
+        LeftRightGuard::new(r, m)
+    }
+
+    pub fn into_inner(self) -> HashMap<K, V> {
+        self.inner.into_inner()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = LeftRightHashMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> From<HashMap<K, V>> for LeftRightHashMap<K, V> {
+    fn from(arg: HashMap<K, V>) -> Self {
+        Self::with_map(arg)
+    }
+}
+
+impl<K, V> serde::Serialize for LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone + Serialize,
+    V: Clone + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.read().serialize(serializer)
+    }
+}
+
+impl<'de, K, V> serde::Deserialize<'de> for LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone + serde::Deserialize<'de>,
+    V: Clone + serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m = HashMap::deserialize(deserializer)?;
+        Ok(Self::with_map(m))
+    }
+}
+
+impl<K, V> Debug for LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Clone + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.read(), f)
+    }
+}
+
+impl<K, V> Display for LeftRightHashMap<K, V>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Clone + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.read(), f)
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Clone for LeftRightHashMap<K, V> {
+    fn clone(&self) -> Self {
+        Self::with_map((*self.read()).clone())
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for LeftRightHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `BTreeMap` counterpart of [`LeftRightHashMap`]: ordered keys, reads
+/// that never wait on writers, two copies of the data.
+///
+/// # Deadlock note
+/// A read guard makes the next writer wait until it is dropped: drop the guard
+/// before writing from the same thread. Debug builds detect this and panic;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy).
+pub struct LeftRightBtreeMap<K, V> {
+    inner: LeftRight<BTreeMap<K, V>>,
+}
+
+impl<K, V> LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    pub fn new_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    pub fn new() -> Self {
+        Self::with_map(BTreeMap::new())
+    }
+
+    pub fn with_map(map: BTreeMap<K, V>) -> Self {
+        Self {
+            inner: LeftRight::new(map, "LeftRightBtreeMap"),
+        }
+    }
+
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        self.write(|m| m.insert(k, v))
+    }
+
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.write(|m| m.remove(k))
+    }
+
+    pub fn clear(&self) {
+        self.write(|m| m.clear())
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, in one batch.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.write(|m| m.retain(f))
+    }
+
+    /// Removes every entry and returns them, in one batch.
+    pub fn drain(&self) -> BTreeMap<K, V> {
+        self.write(|m| m.drain())
+    }
+
+    /// Inserts every entry of `iter` in one batch.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write(|m| {
+            for (k, v) in iter {
+                m.insert(k, v);
+            }
+        })
+    }
+
+    /// Like [`LeftRightBtreeMap::extend`], but returns what [`LeftRightBtreeMap::insert`]
+    /// would have returned for each entry, in order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write(|m| iter.into_iter().map(|(k, v)| m.insert(k, v)).collect())
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty.
+    ///
+    /// The move is not atomic: `other` is drained in one batch and the
+    /// entries are inserted in a second one, so a concurrent reader may find
+    /// an entry in neither map.
+    pub fn append(&self, other: &Self) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let entries = other.drain();
+        self.extend(entries);
+    }
+
+    /// Removes the given keys in one batch and returns the removed values;
+    /// missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        self.write(|m| keys.into_iter().filter_map(|k| m.remove(k)).collect())
+    }
+
+    /// Applies several changes and publishes them together; see
+    /// [`LeftRightHashMap::write`].
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut LeftRightBatch<'_, BTreeMap<K, V>, K, V>) -> R,
+    {
+        self.inner
+            .write(|data, log| f(&mut LeftRightBatch { data, log }))
+    }
+
+    /// Returns a read guard to the value of the key. Never waits, even while
+    /// a write is in progress.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<LeftRightGet<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (r, m) = self.inner.read();
+        m.get(k).map(|v| LeftRightGuard::new(r, v))
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.read().1.contains_key(k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().1.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().1.is_empty()
+    }
+
+    pub fn iter(&self) -> LeftRightBtreeMapIter<'_, K, V> {
+        let (r, m) = self.inner.read();
+        LeftRightIter::new(r, m.iter())
+    }
+
+    /// A read guard to the whole published map.
+    pub fn read(&self) -> LeftRightGuard<'_, BTreeMap<K, V>> {
+        let (r, m) = self.inner.read();
+        LeftRightGuard::new(r, m)
+    }
+
+    pub fn into_inner(self) -> BTreeMap<K, V> {
+        self.inner.into_inner()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = LeftRightBtreeMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> From<BTreeMap<K, V>> for LeftRightBtreeMap<K, V> {
+    fn from(arg: BTreeMap<K, V>) -> Self {
+        Self::with_map(arg)
+    }
+}
+
+impl<K, V> serde::Serialize for LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone + Serialize,
+    V: Clone + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.read().serialize(serializer)
+    }
+}
+
+impl<'de, K, V> serde::Deserialize<'de> for LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone + serde::Deserialize<'de>,
+    V: Clone + serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m = BTreeMap::deserialize(deserializer)?;
+        Ok(Self::with_map(m))
+    }
+}
+
+impl<K, V> Debug for LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone + Debug,
+    V: Clone + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.read(), f)
+    }
+}
+
+impl<K, V> Display for LeftRightBtreeMap<K, V>
+where
+    K: Ord + Clone + Debug,
+    V: Clone + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.read(), f)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Clone for LeftRightBtreeMap<K, V> {
+    fn clone(&self) -> Self {
+        Self::with_map((*self.read()).clone())
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for LeftRightBtreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
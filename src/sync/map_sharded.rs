@@ -0,0 +1,323 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap as Map;
+use std::fmt::{Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+
+use super::map_hash::{HashMapEntry, HashMapGet, HashMapIter, HashMapRefMut};
+use super::SyncHashMap;
+
+/// A [`SyncHashMap`] split into independent shards by key hash.
+///
+/// Each shard is a `SyncHashMap` with its own writer lock and reader slots, so
+/// writes to keys in different shards run in parallel and a writer only waits
+/// for the readers of its own shard. `S` hashes keys to pick the shard.
+///
+/// Whole-map operations (`len`, `iter`, `clear`) visit the shards one by one:
+/// they see each shard consistently, but not all shards at the same instant.
+///
+/// # Deadlock note
+/// As for [`SyncHashMap`]: drop read guards before writing from the same
+/// thread. A guard only blocks writers of its own shard.
+pub struct ShardedSyncHashMap<K: Eq + Hash, V, S = RandomState> {
+    shards: Box<[SyncHashMap<K, V>]>,
+    hasher: S,
+    // `shards.len() - 1`; the shard count is a power of two.
+    mask: usize,
+}
+
+impl<K, V> ShardedSyncHashMap<K, V, RandomState>
+where
+    K: Eq + Hash,
+{
+    pub fn new_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    /// Four shards per available CPU.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// `shard_amount` is rounded up to a power of two.
+    pub fn with_shard_amount(shard_amount: usize) -> Self {
+        Self::with_shard_amount_and_hasher(shard_amount, RandomState::new())
+    }
+}
+
+impl<K, V, S> ShardedSyncHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shard_amount_and_hasher(cpus * 4, hasher)
+    }
+
+    pub fn with_shard_amount_and_hasher(shard_amount: usize, hasher: S) -> Self {
+        let n = shard_amount.max(1).next_power_of_two();
+        Self {
+            shards: (0..n)
+                .map(|_| SyncHashMap::named("ShardedSyncHashMap"))
+                .collect(),
+            hasher,
+            mask: n - 1,
+        }
+    }
+
+    /// The shards, e.g. to lock one for a batch with [`SyncHashMap::write`].
+    pub fn shards(&self) -> &[SyncHashMap<K, V>] {
+        &self.shards
+    }
+
+    /// The shard that holds `k`.
+    #[inline]
+    pub fn shard<Q>(&self, k: &Q) -> &SyncHashMap<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        &self.shards[self.shard_index(k)]
+    }
+
+    #[inline]
+    fn shard_index<Q>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.hasher.hash_one(k) as usize & self.mask
+    }
+
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        self.shard(&k).insert(k, v)
+    }
+
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(k).write(|m| m.remove(k))
+    }
+
+    /// See [`SyncHashMap::get`]. The guard only delays writers of the key's
+    /// shard.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<HashMapGet<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(k).get(k)
+    }
+
+    /// See [`SyncHashMap::entry`]. Only the key's shard is locked.
+    pub fn entry(&self, k: K) -> HashMapEntry<'_, K, V> {
+        self.shard(&k).entry(k)
+    }
+
+    pub fn get_mut(&self, k: &K) -> Option<HashMapRefMut<'_, K, V>> {
+        self.shard(k).get_mut(k)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(k).contains_key(k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    pub fn clear(&self) {
+        for s in self.shards.iter() {
+            s.clear();
+        }
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, one shard at a
+    /// time.
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for s in self.shards.iter() {
+            s.retain(&mut f);
+        }
+    }
+
+    /// Removes every entry and returns them, one shard at a time.
+    pub fn drain(&self) -> std::vec::IntoIter<(K, V)> {
+        let mut entries = Vec::new();
+        for s in self.shards.iter() {
+            entries.extend(s.drain());
+        }
+        entries.into_iter()
+    }
+
+    /// Inserts every entry of `iter`, taking each shard's writer lock once.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut by_shard = self.buckets();
+        for (k, v) in iter {
+            by_shard[self.shard_index(&k)].push((k, v));
+        }
+        for (s, entries) in self.shards.iter().zip(by_shard) {
+            if !entries.is_empty() {
+                s.extend(entries);
+            }
+        }
+    }
+
+    /// Like [`ShardedSyncHashMap::extend`], but returns what
+    /// [`ShardedSyncHashMap::insert`] would have returned for each entry, in
+    /// order.
+    pub fn insert_many<I>(&self, iter: I) -> Vec<Option<V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut order = Vec::new();
+        let mut by_shard = self.buckets();
+        for (k, v) in iter {
+            let i = self.shard_index(&k);
+            order.push(i);
+            by_shard[i].push((k, v));
+        }
+        let olds: Vec<Vec<Option<V>>> = self
+            .shards
+            .iter()
+            .zip(by_shard)
+            .map(|(s, entries)| {
+                if entries.is_empty() {
+                    Vec::new()
+                } else {
+                    s.insert_many(entries)
+                }
+            })
+            .collect();
+        let mut olds: Vec<_> = olds.into_iter().map(|o| o.into_iter()).collect();
+        // every shard hands its results back in insertion order
+        order.into_iter().map(|i| olds[i].next().unwrap()).collect()
+    }
+
+    /// Moves every entry of `other` into this map, leaving `other` empty.
+    ///
+    /// The move is not atomic: `other` is drained one shard at a time and the
+    /// entries are inserted afterwards, so a concurrent reader may find an
+    /// entry in neither map.
+    pub fn append(&self, other: &Self) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        self.extend(other.drain());
+    }
+
+    /// Removes the given keys, taking each shard's writer lock once, and
+    /// returns the removed values grouped by shard; missing keys are skipped.
+    pub fn remove_many<'q, Q, I>(&self, keys: I) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        let mut by_shard = self.buckets();
+        for k in keys {
+            by_shard[self.shard_index(k)].push(k);
+        }
+        let mut removed = Vec::new();
+        for (s, keys) in self.shards.iter().zip(by_shard) {
+            if !keys.is_empty() {
+                removed.extend(s.remove_many(keys));
+            }
+        }
+        removed
+    }
+
+    /// One empty bucket per shard.
+    fn buckets<T>(&self) -> Vec<Vec<T>> {
+        (0..self.shards.len()).map(|_| Vec::new()).collect()
+    }
+
+    /// Iterates the shards in turn. A shard is read-locked when the iterator
+    /// reaches it and stays locked until the iterator is dropped.
+    pub fn iter(&self) -> ShardedHashMapIter<'_, K, V> {
+        ShardedHashMapIter {
+            shards: self.shards.iter(),
+            current: None,
+            done: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> Map<K, V> {
+        let mut map = Map::with_capacity(self.len());
+        for s in self.shards.into_vec() {
+            map.extend(s.into_inner());
+        }
+        map
+    }
+}
+
+/// Read iterator returned by [`ShardedSyncHashMap::iter`].
+pub struct ShardedHashMapIter<'a, K: Eq + Hash, V> {
+    shards: std::slice::Iter<'a, SyncHashMap<K, V>>,
+    current: Option<HashMapIter<'a, K, V>>,
+    // Iterators of the shards already visited: they keep the shards
+    // read-locked while their entries may still be borrowed.
+    done: Vec<HashMapIter<'a, K, V>>,
+}
+
+impl<'a, K: Eq + Hash, V> Iterator for ShardedHashMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(it) = &mut self.current {
+                if let Some(kv) = it.next() {
+                    return Some(kv);
+                }
+            }
+            let next = self.shards.next()?.iter();
+            if let Some(prev) = self.current.replace(next) {
+                self.done.push(prev);
+            }
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash, V, S: BuildHasher> IntoIterator for &'a ShardedSyncHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = ShardedHashMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> Debug for ShardedSyncHashMap<K, V, S>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Eq + Hash, V> Default for ShardedSyncHashMap<K, V, RandomState> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,18 +1,42 @@
+pub mod cell;
+pub mod deadlock;
 pub mod map_btree;
 pub mod map_hash;
 pub mod map_index;
+pub mod map_left_right;
+pub mod map_sharded;
+pub mod ring_buffer;
+pub mod tx;
 pub mod vec;
+pub mod vec_deque;
 pub mod wg;
 
 pub mod duration;
 
-use parking_lot::{Mutex, MutexGuard};
+mod cow;
+mod left_right;
+mod stats;
+mod wait;
+
+use parking_lot::lock_api::RawMutex as _;
+use parking_lot::lock_api::RawMutexTimed as _;
+use parking_lot::{Mutex, RawMutex};
+use stats::Counters;
 use std::boxed::Box;
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{BuildHasherDefault, Hasher};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use wait::WaitList;
+
+use crate::err;
+use crate::errors::Error;
 
 /// Per-thread reader slots.
 ///
@@ -21,59 +45,577 @@ use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 /// owned by the container and never moves). The counter is only written by its
 /// owning thread, so concurrent readers touch their own cache line and never
 /// contend with each other; writers scan the registry to know when all readers
-/// are gone. Entries for dropped containers are simply never used again (each
-/// container has a unique `id`, so address reuse cannot alias a stale entry).
+/// are gone. When the thread exits, its slots are handed back to the
+/// registries of the containers that are still alive, so short-lived threads
+/// do not make registries grow without bound.
+///
+/// Entries are keyed by container id, so a lookup is O(1) however many
+/// containers the thread touches. Entries of dropped containers are purged
+/// lazily: every registry drop bumps [`DROPPED`], and a thread that sees a new
+/// epoch while registering drops the entries whose registry is gone.
 struct ReaderSlots {
     // Fast path: most threads read only one container, so cache the last
-    // (container id -> counter) pair to avoid scanning the vector every read.
+    // (container id -> counter) pair to avoid hashing on every read.
     last: Cell<(usize, *const AtomicUsize)>,
-    all: RefCell<Vec<(usize, *const AtomicUsize)>>,
+    all: RefCell<HashMap<usize, SlotRef, BuildHasherDefault<IdHasher>>>,
+    // The `DROPPED` epoch seen by the last purge, and the table size that
+    // triggers the next one (doubling keeps purging amortised O(1)).
+    purged: Cell<usize>,
+    purge_at: Cell<usize>,
+}
+
+/// A thread's claim on one slot of a container's registry.
+struct SlotRef {
+    count: *const AtomicUsize,
+    index: usize,
+    registry: Weak<Registry>,
+}
+
+impl Drop for ReaderSlots {
+    fn drop(&mut self) {
+        for (_, slot) in self.all.get_mut().drain() {
+            if let Some(registry) = slot.registry.upgrade() {
+                registry.release(slot.index);
+            }
+        }
+    }
+}
+
+impl ReaderSlots {
+    /// Forgets the slots of dropped containers if any container was dropped
+    /// since the last purge and the table has grown enough to be worth it.
+    fn purge(&self, all: &mut HashMap<usize, SlotRef, BuildHasherDefault<IdHasher>>) {
+        let epoch = DROPPED.load(Ordering::Relaxed);
+        if epoch == self.purged.get() || all.len() < self.purge_at.get() {
+            return;
+        }
+        all.retain(|_, slot| slot.registry.strong_count() != 0);
+        self.purged.set(epoch);
+        self.purge_at.set((all.len() * 2).max(PURGE_MIN));
+    }
+}
+
+/// Table size below which dropped entries are not worth purging.
+const PURGE_MIN: usize = 16;
+
+/// Bumped every time a container's registry is dropped.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Hasher for container ids: ids are sequential, so a Fibonacci multiply is
+/// enough to spread them over the table.
+#[derive(Default)]
+struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn write_usize(&mut self, id: usize) {
+        self.0 = (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
 }
 
 thread_local! {
     static SLOTS: ReaderSlots = ReaderSlots {
         last: Cell::new((usize::MAX, std::ptr::null())),
-        all: RefCell::new(Vec::new()),
+        all: RefCell::new(HashMap::default()),
+        purged: Cell::new(0),
+        purge_at: Cell::new(PURGE_MIN),
     };
 }
 
+/// The registry of per-thread reader counters of one container.
+///
+/// The counters are boxed so their addresses stay stable while the vector
+/// grows. Slots released by exited threads go to `free` and are handed to the
+/// next thread that registers, so the registry is bounded by the number of
+/// threads reading concurrently rather than by every thread that ever read.
+pub(crate) struct Registry {
+    slots: Mutex<Slots>,
+    // Fallback counter for readers that cannot use a thread-local slot (e.g.
+    // reads from another thread-local's destructor during thread teardown).
+    shared: AtomicUsize,
+}
+
+#[allow(clippy::vec_box)]
+struct Slots {
+    all: Vec<Box<AtomicUsize>>,
+    free: Vec<usize>,
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Registry {
+    pub(crate) fn new() -> Self {
+        Registry {
+            slots: Mutex::new(Slots {
+                all: Vec::new(),
+                free: Vec::new(),
+            }),
+            shared: AtomicUsize::new(0),
+        }
+    }
+
+    /// Claims a free slot (or appends a new one) for the calling thread.
+    fn acquire(&self) -> (usize, *const AtomicUsize) {
+        let mut slots = self.slots.lock();
+        let index = match slots.free.pop() {
+            Some(index) => index,
+            None => {
+                slots.all.push(Box::new(AtomicUsize::new(0)));
+                slots.all.len() - 1
+            }
+        };
+        (index, &*slots.all[index])
+    }
+
+    /// Hands the slot at `index` back for reuse. A guard that was moved to
+    /// another thread may still hold a count in it; that is fine, since the
+    /// next owner only adds to and subtracts from the same counter.
+    fn release(&self, index: usize) {
+        self.slots.lock().free.push(index);
+    }
+
+    /// `true` when no reader holds any slot of this registry.
+    pub(crate) fn drained(&self) -> bool {
+        let slots = self.slots.lock();
+        self.shared.load(Ordering::SeqCst) == 0
+            && slots.all.iter().all(|c| c.load(Ordering::SeqCst) == 0)
+    }
+
+    /// (slots held by at least one reader, slots allocated so far).
+    #[cfg(feature = "stats")]
+    fn occupancy(&self) -> (usize, usize) {
+        let slots = self.slots.lock();
+        let held = slots
+            .all
+            .iter()
+            .filter(|c| c.load(Ordering::Relaxed) != 0)
+            .count();
+        let shared = (self.shared.load(Ordering::Relaxed) != 0) as usize;
+        (held + shared, slots.all.len())
+    }
+
+    /// Number of slots allocated so far (live and free).
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.slots.lock().all.len()
+    }
+}
+
 /// Returns (and lazily registers) the current thread's reader counter for the
-/// container identified by `id`, adding it to `registry` on first use. The
-/// returned reference is valid for as long as the registry (i.e. the
+/// container identified by `id`, claiming a slot in `registry` on first use.
+/// The returned reference is valid for as long as the registry (i.e. the
 /// container) lives.
-pub(crate) fn reader_count_for<'a>(
-    id: usize,
-    registry: &'a Mutex<Vec<Box<AtomicUsize>>>,
-) -> &'a AtomicUsize {
-    SLOTS.with(|slots| {
+pub(crate) fn reader_count_for(id: usize, registry: &Arc<Registry>) -> &AtomicUsize {
+    let slot = SLOTS.try_with(|slots| {
         let (last_id, last_ptr) = slots.last.get();
         if last_id == id && !last_ptr.is_null() {
-            // SAFETY: the counter lives in `registry`, which is borrowed for
-            // 'a and never removes entries, so the Box address stays valid.
-            return unsafe { &*last_ptr };
+            return last_ptr;
         }
         let mut all = slots.all.borrow_mut();
-        if let Some((_, c)) = all.iter().find(|(k, _)| *k == id) {
-            slots.last.set((id, *c));
-            // SAFETY: the counter lives in `registry`, borrowed for 'a, and
-            // registry entries are never removed, so the address stays valid.
-            return unsafe { &**c };
+        if let Some(slot) = all.get(&id) {
+            slots.last.set((id, slot.count));
+            return slot.count;
         }
-        let mut reg = registry.lock();
-        reg.push(Box::new(AtomicUsize::new(0)));
-        let ptr: *const AtomicUsize = &**reg.last().unwrap();
-        all.push((id, ptr));
-        slots.last.set((id, ptr));
-        // SAFETY: the newly pushed Box is in `registry` (borrowed for 'a) and
-        // never moves, so this reference stays valid for 'a.
-        unsafe { &*ptr }
-    })
+        slots.purge(&mut all);
+        let (index, count) = registry.acquire();
+        all.insert(
+            id,
+            SlotRef {
+                count,
+                index,
+                registry: Arc::downgrade(registry),
+            },
+        );
+        slots.last.set((id, count));
+        count
+    });
+    match slot {
+        // SAFETY: the counter is boxed in `registry`, which outlives the
+        // returned borrow and never frees slots (released ones are only
+        // reused), so the Box address stays valid.
+        Ok(count) => unsafe { &*count },
+        // The thread-local is already destroyed: use the shared counter.
+        Err(_) => &registry.shared,
+    }
+}
+
+/// `true` if the current thread holds a read guard on the container
+/// identified by `id`. Lookup only: never registers a slot.
+fn reading_on_current_thread(id: usize) -> bool {
+    SLOTS
+        .try_with(|slots| {
+            let (last_id, last_ptr) = slots.last.get();
+            let count = if last_id == id && !last_ptr.is_null() {
+                last_ptr
+            } else {
+                match slots.all.borrow().get(&id) {
+                    Some(slot) => slot.count,
+                    None => return false,
+                }
+            };
+            // SAFETY: the entry for `id` is only present while the caller's
+            // container (and so its registry) is alive.
+            unsafe { &*count }.load(Ordering::SeqCst) != 0
+        })
+        .unwrap_or(false)
+}
+
+/// Applies the [`DeadlockPolicy`] to a write on the container `name` when the
+/// calling thread holds a read guard on any of the reader ids in `ids`.
+#[inline]
+fn check_reentrant_write(name: &'static str, ids: &[usize]) -> Result<(), Error> {
+    let policy = deadlock_policy();
+    if policy == DeadlockPolicy::Block || !ids.iter().any(|id| reading_on_current_thread(*id)) {
+        return Ok(());
+    }
+    let msg = format!(
+        "{}: write while the current thread holds a read guard on the same {} \
+         would deadlock; drop the guard first",
+        name, name
+    );
+    match policy {
+        DeadlockPolicy::Panic => panic!("{}", msg),
+        _ => Err(err!("Deadlock: {}", msg)),
+    }
 }
 
 /// Unique id source for containers, so a thread-local slot can never alias a
 /// different container that happens to reuse the same memory address.
 pub(crate) static CONTAINER_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// The read/write-separation engine shared by every synchronous container.
+///
+/// Readers bump their own per-thread counter (see [`reader_count_for`]) and
+/// back off while `writing` is set; writers serialize on `write`, raise
+/// `writing` and wait until every registered counter has drained. Waiting
+/// threads spin briefly and then park (async callers register a waker) on
+/// `waiters`, which is notified whenever the writer lock is released or a
+/// reader leaves while a writer is waiting.
+///
+/// `write_timeout` lets the non-blocking writers wait that long before they
+/// give up; `None` (the default) makes them fail at once.
+pub(crate) struct RawLock {
+    write: RawMutex,
+    id: usize,
+    // Container type name, for deadlock diagnostics.
+    name: &'static str,
+    writing: AtomicBool,
+    registry: Arc<Registry>,
+    waiters: WaitList,
+    write_timeout: AtomicDuration,
+    stats: Counters,
+}
+
+impl RawLock {
+    pub(crate) fn new(name: &'static str) -> Self {
+        RawLock {
+            write: RawMutex::INIT,
+            id: CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            writing: AtomicBool::new(false),
+            registry: Arc::new(Registry::new()),
+            waiters: WaitList::new(),
+            write_timeout: AtomicDuration::new(None),
+            stats: Counters::default(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn begin_read(&self) -> ReadLock<'_> {
+        // The counter lives in thread-local storage: concurrent readers only
+        // touch their own cache line and never contend with each other.
+        let count = reader_count_for(self.id, &self.registry);
+        self.read_with(count)
+    }
+
+    /// Like [`RawLock::begin_read`], but pins the registry's shared counter
+    /// instead of the calling thread's slot, so the returned lock may be
+    /// released on any thread. Readers that use it contend on one cache line,
+    /// and the same-thread deadlock check cannot see them.
+    #[inline]
+    pub(crate) fn begin_read_send(&self) -> ReadLock<'_> {
+        self.read_with(&self.registry.shared)
+    }
+
+    #[inline]
+    fn read_with<'a>(&'a self, count: &'a AtomicUsize) -> ReadLock<'a> {
+        // SeqCst closes the store-buffering window with the writer's all-zero
+        // scan.
+        loop {
+            count.fetch_add(1, Ordering::SeqCst);
+            if !self.writing.load(Ordering::SeqCst) {
+                self.stats.read();
+                return ReadLock { raw: self, count };
+            }
+            self.end_read(count);
+            self.stats.read_retry();
+            self.waiters
+                .block_until(|| !self.writing.load(Ordering::SeqCst));
+        }
+    }
+
+    /// Async counterpart of [`RawLock::begin_read`]: awaits the end of an
+    /// active write instead of yielding the OS thread.
+    pub(crate) async fn begin_read_async(&self) -> ReadLock<'_> {
+        loop {
+            // Look the counter up again after every await: the task may have
+            // been moved to another thread in the meantime.
+            let count = reader_count_for(self.id, &self.registry);
+            count.fetch_add(1, Ordering::SeqCst);
+            if !self.writing.load(Ordering::SeqCst) {
+                self.stats.read();
+                return ReadLock { raw: self, count };
+            }
+            self.end_read(count);
+            self.stats.read_retry();
+            self.waiters
+                .wait_until(|| !self.writing.load(Ordering::SeqCst))
+                .await;
+        }
+    }
+
+    /// Releases one reader slot. The last reader to leave a slot wakes the
+    /// waiting writer, if any; earlier departures cannot let it in anyway.
+    #[inline]
+    fn end_read(&self, count: &AtomicUsize) {
+        if count.fetch_sub(1, Ordering::SeqCst) == 1 && self.writing.load(Ordering::SeqCst) {
+            self.waiters.notify_all();
+        }
+    }
+
+    /// Acquires the writer lock and waits for readers to drain.
+    #[inline]
+    pub(crate) fn begin_write(&self) -> WriteLock<'_> {
+        self.begin_write_with(|| {})
+    }
+
+    /// [`RawLock::begin_write`] that runs `prepare` once it holds the writer
+    /// lock but before it holds off readers: other writers are excluded, so
+    /// `prepare` may do slow, read-only work on the data (e.g. copy it)
+    /// without stalling the readers.
+    #[inline]
+    pub(crate) fn begin_write_with(&self, prepare: impl FnOnce()) -> WriteLock<'_> {
+        if let Err(e) = self.check_reentrant_write() {
+            panic!("{}", e);
+        }
+        let start = self.stats.write_start();
+        self.write.lock();
+        let w = WriteLock { raw: self };
+        prepare();
+        self.writing.store(true, Ordering::SeqCst);
+        self.waiters.block_until(|| self.readers_drained());
+        self.stats.write(start);
+        w
+    }
+
+    /// Bounded counterpart of [`RawLock::begin_write`]: gives up once `timeout`
+    /// has elapsed, either waiting for the writer lock or for readers to
+    /// drain. On failure `writing` is cleared so blocked readers resume.
+    pub(crate) fn begin_write_timeout(&self, timeout: Duration) -> Result<WriteLock<'_>, Error> {
+        self.begin_write_timeout_with(timeout, || {})
+    }
+
+    /// [`RawLock::begin_write_timeout`] with a `prepare` step, see
+    /// [`RawLock::begin_write_with`].
+    pub(crate) fn begin_write_timeout_with(
+        &self,
+        timeout: Duration,
+        prepare: impl FnOnce(),
+    ) -> Result<WriteLock<'_>, Error> {
+        self.check_reentrant_try_write()?;
+        let start = self.stats.write_start();
+        let deadline = Instant::now() + timeout;
+        if !self.write.try_lock_until(deadline) {
+            return Err(err!(
+                "Timeout: the writer lock was not acquired within {:?}",
+                timeout
+            ));
+        }
+        let w = WriteLock { raw: self };
+        prepare();
+        self.writing.store(true, Ordering::SeqCst);
+        if !self
+            .waiters
+            .block_until_deadline(|| self.readers_drained(), deadline)
+        {
+            // dropping `w` clears `writing` and wakes the readers it held off
+            return Err(err!("Timeout: readers did not drain within {:?}", timeout));
+        }
+        self.stats.write(start);
+        Ok(w)
+    }
+
+    #[inline]
+    pub(crate) fn default_write_timeout(&self) -> Option<Duration> {
+        self.write_timeout.get()
+    }
+
+    #[inline]
+    pub(crate) fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.write_timeout.store(timeout);
+    }
+
+    /// Applies the [`DeadlockPolicy`] when the calling thread still holds a
+    /// read guard on this container, which the write would wait for forever.
+    #[inline]
+    fn check_reentrant_write(&self) -> Result<(), Error> {
+        check_reentrant_write(self.name, &[self.id])
+    }
+
+    /// [`RawLock::check_reentrant_write`] for writes that give up on their own
+    /// (`try_*`, `*_timeout`): only [`DeadlockPolicy::Error`] reports the
+    /// deadlock, otherwise they fail with `WouldBlock` or `Timeout` as usual.
+    #[inline]
+    fn check_reentrant_try_write(&self) -> Result<(), Error> {
+        if deadlock_policy() != DeadlockPolicy::Error {
+            return Ok(());
+        }
+        self.check_reentrant_write()
+    }
+
+    /// Non-blocking counterpart of [`RawLock::begin_write`]: fails instead of
+    /// waiting when another writer holds the lock or readers are active. With
+    /// a default write timeout set, waits up to that long instead, see
+    /// [`RawLock::begin_write_timeout`].
+    pub(crate) fn try_begin_write(&self) -> Result<WriteLock<'_>, Error> {
+        self.try_begin_write_with(|| {})
+    }
+
+    /// [`RawLock::try_begin_write`] with a `prepare` step, see
+    /// [`RawLock::begin_write_with`].
+    pub(crate) fn try_begin_write_with(
+        &self,
+        prepare: impl FnOnce(),
+    ) -> Result<WriteLock<'_>, Error> {
+        if let Some(timeout) = self.write_timeout.get() {
+            return self.begin_write_timeout_with(timeout, prepare);
+        }
+        self.check_reentrant_try_write()?;
+        let start = self.stats.write_start();
+        if !self.write.try_lock() {
+            return Err(err!("WouldBlock: the writer lock is held"));
+        }
+        let w = WriteLock { raw: self };
+        prepare();
+        self.writing.store(true, Ordering::SeqCst);
+        if !self.readers_drained() {
+            // dropping `w` clears `writing` and wakes the readers it held off
+            return Err(err!("WouldBlock: readers are active"));
+        }
+        self.stats.write(start);
+        Ok(w)
+    }
+
+    /// Async counterpart of [`RawLock::begin_write`]: awaits the writer lock
+    /// and the reader drain instead of blocking or yielding the OS thread.
+    pub(crate) async fn begin_write_async(&self) -> WriteLock<'_> {
+        self.begin_write_async_with(|| {}).await
+    }
+
+    /// [`RawLock::begin_write_async`] with a `prepare` step, see
+    /// [`RawLock::begin_write_with`].
+    pub(crate) async fn begin_write_async_with(&self, prepare: impl FnOnce()) -> WriteLock<'_> {
+        let start = self.stats.write_start();
+        self.waiters.wait_until(|| self.write.try_lock()).await;
+        let w = WriteLock { raw: self };
+        prepare();
+        self.writing.store(true, Ordering::SeqCst);
+        self.waiters.wait_until(|| self.readers_drained()).await;
+        self.stats.write(start);
+        w
+    }
+
+    /// Takes the writer lock without raising `writing`: other writers wait,
+    /// readers do not. See [`UpgradableLock::upgrade`].
+    pub(crate) fn begin_upgradable(&self) -> UpgradableLock<'_> {
+        self.write.lock();
+        UpgradableLock { raw: self }
+    }
+
+    fn readers_drained(&self) -> bool {
+        self.registry.drained()
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn stats(&self) -> Stats {
+        let (active, slots) = self.registry.occupancy();
+        self.stats.snapshot(active, slots)
+    }
+}
+
+/// Internal RAII token for an upgradable read: holds the writer mutex, so no
+/// writer can run, but leaves `writing` clear so readers keep going.
+pub(crate) struct UpgradableLock<'a> {
+    raw: &'a RawLock,
+}
+
+impl<'a> UpgradableLock<'a> {
+    /// Raises `writing` and waits for readers to drain. The writer mutex is
+    /// never released in between, so no other writer can slip in.
+    pub(crate) fn upgrade(self) -> WriteLock<'a> {
+        let raw = self.raw;
+        if let Err(e) = raw.check_reentrant_write() {
+            panic!("{}", e);
+        }
+        // ownership of the locked mutex moves to the `WriteLock`
+        std::mem::forget(self);
+        let start = raw.stats.write_start();
+        let w = WriteLock { raw };
+        raw.writing.store(true, Ordering::SeqCst);
+        raw.waiters.block_until(|| raw.readers_drained());
+        raw.stats.write(start);
+        w
+    }
+}
+
+impl<'a> Drop for UpgradableLock<'a> {
+    fn drop(&mut self) {
+        // SAFETY: an `UpgradableLock` is only created right after `write` has
+        // been locked, and it unlocks it exactly once (unless upgraded).
+        unsafe { self.raw.write.unlock() };
+        self.raw.waiters.notify_all();
+    }
+}
+
+/// Internal RAII token for the read path: holds one reader slot in the calling
+/// thread's counter and releases it when dropped.
+pub(crate) struct ReadLock<'a> {
+    raw: &'a RawLock,
+    count: &'a AtomicUsize,
+}
+
+impl ReadLock<'_> {
+    /// Erases the borrow of the lock's container.
+    ///
+    /// # Safety
+    /// The container must outlive the returned lock, e.g. because the lock is
+    /// stored next to (and dropped before) an `Arc` that owns it.
+    #[inline]
+    pub(crate) unsafe fn detach(self) -> ReadLock<'static> {
+        std::mem::transmute::<ReadLock<'_>, ReadLock<'static>>(self)
+    }
+}
+
+impl<'a> Drop for ReadLock<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        self.raw.end_read(self.count);
+    }
+}
+
 /// An RAII read guard returned by the `get` methods of the synchronous
 /// containers (`SyncHashMap`, `SyncBtreeMap`, `SyncVec`, `SyncIndexMap`).
 ///
@@ -83,17 +625,23 @@ pub(crate) static CONTAINER_ID: AtomicUsize = AtomicUsize::new(0);
 /// never be invalidated or raced while the guard is alive. It is not `Send`:
 /// it must be dropped on the same thread that created it.
 pub struct ReadGuard<'a, V> {
-    count: &'a AtomicUsize,
-    value: &'a V,
+    _r: ReadLock<'a>,
+    // A pointer rather than `&'a V` (as in `std::cell::Ref`): a reference in
+    // a guard passed by value would be asserted live for the whole call, even
+    // after the callee drops the guard and a writer mutates the value. All
+    // guards below store their value the same way.
+    value: NonNull<V>,
+    _marker: PhantomData<&'a V>,
     _not_send: PhantomData<*const ()>,
 }
 
 impl<'a, V> ReadGuard<'a, V> {
     #[inline]
-    pub(crate) fn new(count: &'a AtomicUsize, value: &'a V) -> Self {
+    pub(crate) fn new(_r: ReadLock<'a>, value: &'a V) -> Self {
         ReadGuard {
-            count,
-            value,
+            _r,
+            value: NonNull::from(value),
+            _marker: PhantomData,
             _not_send: PhantomData,
         }
     }
@@ -104,25 +652,20 @@ impl<'a, V> Deref for ReadGuard<'a, V> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.value
-    }
-}
-
-impl<'a, V> Drop for ReadGuard<'a, V> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
+        // SAFETY: the lock held by the guard keeps writers away from `value`.
+        unsafe { self.value.as_ref() }
     }
 }
 
 impl<'a, V: Debug> Debug for ReadGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(self.value, f)
+        Debug::fmt(&**self, f)
     }
 }
 
 impl<'a, V: Display> Display for ReadGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self.value, f)
+        Display::fmt(&**self, f)
     }
 }
 
@@ -146,89 +689,444 @@ impl<'a, V: PartialEq> PartialEq<&V> for ReadGuard<'a, V> {
     }
 }
 
-/// A read guard for whole-container access (`iter`, `dirty_ref`, ...).
+/// A read guard on one map entry, key included, returned by the positional
+/// and ordered lookups of `SyncBtreeMap` and `SyncIndexMap` (`first`,
+/// `get_index`, `floor`, ...). Derefs to the value; otherwise like
+/// [`ReadGuard`].
+pub struct ReadEntryGuard<'a, K, V> {
+    _r: ReadLock<'a>,
+    key: NonNull<K>,
+    value: NonNull<V>,
+    _marker: PhantomData<(&'a K, &'a V)>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, K, V> ReadEntryGuard<'a, K, V> {
+    #[inline]
+    pub(crate) fn new(_r: ReadLock<'a>, (key, value): (&'a K, &'a V)) -> Self {
+        ReadEntryGuard {
+            _r,
+            key: NonNull::from(key),
+            value: NonNull::from(value),
+            _marker: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        // SAFETY: the lock held by the guard keeps writers away from the entry.
+        unsafe { self.key.as_ref() }
+    }
+
+    #[inline]
+    pub fn value(&self) -> &V {
+        // SAFETY: as above.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<'a, K, V> Deref for ReadEntryGuard<'a, K, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.value()
+    }
+}
+
+impl<'a, K: Debug, V: Debug> Debug for ReadEntryGuard<'a, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("")
+            .field(self.key())
+            .field(self.value())
+            .finish()
+    }
+}
+
+/// A read guard for whole-container access (`dirty_ref`, `SyncCell::read`,
+/// ...).
 ///
 /// Reading is lock-free and contention-free; the guard only pins a reader slot
 /// in the calling thread's private counter. It is not `Send`: it must be
 /// dropped on the same thread that created it.
-pub struct ReadMapGuard<'a, C> {
-    count: &'a AtomicUsize,
-    value: &'a C,
+pub struct ReadMapGuard<'a, C: ?Sized> {
+    _r: ReadLock<'a>,
+    value: NonNull<C>,
+    _marker: PhantomData<&'a C>,
     _not_send: PhantomData<*const ()>,
 }
 
-impl<'a, C> ReadMapGuard<'a, C> {
+impl<'a, C: ?Sized> ReadMapGuard<'a, C> {
     #[inline]
-    pub(crate) fn new(count: &'a AtomicUsize, value: &'a C) -> Self {
+    pub(crate) fn new(_r: ReadLock<'a>, value: &'a C) -> Self {
         ReadMapGuard {
-            count,
-            value,
+            _r,
+            value: NonNull::from(value),
+            _marker: PhantomData,
             _not_send: PhantomData,
         }
     }
 }
 
-impl<'a, C> Deref for ReadMapGuard<'a, C> {
+impl<'a, C: ?Sized> Deref for ReadMapGuard<'a, C> {
     type Target = C;
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.value
+        // SAFETY: the lock held by the guard keeps writers away from `value`.
+        unsafe { self.value.as_ref() }
     }
 }
 
-impl<'a, C> Drop for ReadMapGuard<'a, C> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
+impl<'a, C: Debug + ?Sized> Debug for ReadMapGuard<'a, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, C: Display + ?Sized> Display for ReadMapGuard<'a, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+/// A `Send` read guard returned by the `get_send` methods of the synchronous
+/// containers.
+///
+/// Unlike [`ReadGuard`] it pins the container's shared reader counter rather
+/// than the calling thread's slot, so it can be held across an `.await` in a
+/// multi-threaded runtime and dropped on any thread. The shared counter is one
+/// cache line for all such readers: prefer `get` when the guard stays on one
+/// thread. Like any read guard it makes writers wait until it is dropped, and
+/// a write from a thread that holds one is not detected as a deadlock.
+pub struct SendReadGuard<'a, V> {
+    _r: ReadLock<'a>,
+    value: NonNull<V>,
+    _marker: PhantomData<&'a V>,
+}
+
+// SAFETY: the guard only hands out `&V` and its lock pins the shared counter,
+// which any thread may release.
+unsafe impl<'a, V: Sync> Send for SendReadGuard<'a, V> {}
+unsafe impl<'a, V: Sync> Sync for SendReadGuard<'a, V> {}
+
+impl<'a, V> SendReadGuard<'a, V> {
+    #[inline]
+    pub(crate) fn new(_r: ReadLock<'a>, value: &'a V) -> Self {
+        SendReadGuard {
+            _r,
+            value: NonNull::from(value),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, V> Deref for SendReadGuard<'a, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the lock held by the guard keeps writers away from `value`.
+        unsafe { self.value.as_ref() }
     }
 }
 
-impl<'a, C: Debug> Debug for ReadMapGuard<'a, C> {
+impl<'a, V: Debug> Debug for SendReadGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(self.value, f)
+        Debug::fmt(&**self, f)
     }
 }
 
-impl<'a, C: Display> Display for ReadMapGuard<'a, C> {
+impl<'a, V: Display> Display for SendReadGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self.value, f)
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, V: PartialEq> PartialEq for SendReadGuard<'a, V> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, V: Eq> Eq for SendReadGuard<'a, V> {}
+
+impl<'a, V: PartialEq> PartialEq<V> for SendReadGuard<'a, V> {
+    fn eq(&self, other: &V) -> bool {
+        **self == *other
+    }
+}
+
+/// A `Send` whole-container read guard (`dirty_ref_send`,
+/// `SyncCell::read_send`); see [`SendReadGuard`].
+pub struct SendReadMapGuard<'a, C> {
+    _r: ReadLock<'a>,
+    value: NonNull<C>,
+    _marker: PhantomData<&'a C>,
+}
+
+// SAFETY: as for `SendReadGuard`.
+unsafe impl<'a, C: Sync> Send for SendReadMapGuard<'a, C> {}
+unsafe impl<'a, C: Sync> Sync for SendReadMapGuard<'a, C> {}
+
+impl<'a, C> SendReadMapGuard<'a, C> {
+    #[inline]
+    pub(crate) fn new(_r: ReadLock<'a>, value: &'a C) -> Self {
+        SendReadMapGuard {
+            _r,
+            value: NonNull::from(value),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C> Deref for SendReadMapGuard<'a, C> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the lock held by the guard keeps writers away from `value`.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<'a, C: Debug> Debug for SendReadMapGuard<'a, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, C: Display> Display for SendReadMapGuard<'a, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+/// An owned read guard returned by the `get_owned`/`read_owned` methods of an
+/// `Arc`-wrapped container, like tokio's `OwnedRwLockReadGuard`.
+///
+/// It borrows nothing: it keeps the container alive through its `Arc`, so it
+/// can be stored in a struct, returned from a function or moved into a
+/// spawned task. It pins the container's shared reader counter (see
+/// [`SendReadGuard`]) and releases it on drop, on any thread.
+pub struct OwnedReadGuard<C, V> {
+    // Borrows from `_owner`: declared first so it is dropped first.
+    _r: ReadLock<'static>,
+    value: NonNull<V>,
+    _owner: Arc<C>,
+}
+
+// SAFETY: the guard hands out `&V` and keeps an `Arc<C>`; its lock pins the
+// shared counter, which any thread may release.
+unsafe impl<C: Send + Sync, V: Sync> Send for OwnedReadGuard<C, V> {}
+unsafe impl<C: Send + Sync, V: Sync> Sync for OwnedReadGuard<C, V> {}
+
+impl<C, V> OwnedReadGuard<C, V> {
+    /// # Safety
+    /// `r` must be a read lock of `owner` taken with `begin_read_send`, and
+    /// `value` must point into the data it protects.
+    #[inline]
+    pub(crate) unsafe fn new(owner: Arc<C>, r: ReadLock<'_>, value: &V) -> Self {
+        OwnedReadGuard {
+            _r: r.detach(),
+            value: NonNull::from(value),
+            _owner: owner,
+        }
+    }
+}
+
+impl<C, V> Deref for OwnedReadGuard<C, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the lock held by the guard keeps writers away from `value`,
+        // and `_owner` keeps the container alive.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<C, V: Debug> Debug for OwnedReadGuard<C, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<C, V: Display> Display for OwnedReadGuard<C, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<C, V: PartialEq> PartialEq<V> for OwnedReadGuard<C, V> {
+    fn eq(&self, other: &V) -> bool {
+        **self == *other
+    }
+}
+
+/// An owned iterator returned by the `iter_owned` methods of an `Arc`-wrapped
+/// container. It holds a read lock like [`OwnedReadGuard`] for as long as it
+/// lives and yields clones of the items, since it cannot lend out references
+/// to itself.
+pub struct OwnedIter<C, I> {
+    inner: I,
+    // Both borrow from `_owner`: declared first so they are dropped first.
+    _r: ReadLock<'static>,
+    _owner: Arc<C>,
+}
+
+impl<C, I> OwnedIter<C, I> {
+    /// # Safety
+    /// `r` must be a read lock of `owner` taken with `begin_read_send`, and
+    /// `inner` must iterate the data it protects.
+    #[inline]
+    pub(crate) unsafe fn new(owner: Arc<C>, r: ReadLock<'_>, inner: I) -> Self {
+        OwnedIter {
+            inner,
+            _r: r.detach(),
+            _owner: owner,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn inner(&mut self) -> &mut I {
+        &mut self.inner
+    }
+}
+
+/// An upgradable read guard returned by the `upgradable_read` methods of the
+/// synchronous containers.
+///
+/// At most one upgradable guard (or writer) exists per container at a time:
+/// it holds the writer lock, so other writers wait, but plain readers keep
+/// reading alongside it. [`UpgradableReadGuard::upgrade`] atomically turns it
+/// into a [`WriteGuard`] once those readers have drained, so a value that was
+/// read cannot be changed by another writer before it is written. Do not call
+/// a write method of the same container while holding one: the write waits
+/// for the guard and deadlocks.
+pub struct UpgradableReadGuard<'a, C> {
+    _u: UpgradableLock<'a>,
+    value: NonNull<C>,
+    unshare: Option<Unshare<C>>,
+    _marker: PhantomData<&'a mut C>,
+}
+
+/// The container, and the function that returns its data ready for writing
+/// (e.g. copied out of a snapshot that still shares it).
+type Unshare<C> = (*const (), unsafe fn(*const ()) -> *mut C);
+
+impl<'a, C> UpgradableReadGuard<'a, C> {
+    /// Takes the raw pointer rather than `&mut C`: plain readers may still
+    /// hold shared references into the value until the guard is upgraded.
+    #[inline]
+    pub(crate) fn new(_u: UpgradableLock<'a>, value: *mut C) -> Self {
+        UpgradableReadGuard {
+            _u,
+            // SAFETY: `value` comes from a container's `UnsafeCell`.
+            value: unsafe { NonNull::new_unchecked(value) },
+            unshare: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes `upgrade` write to `unshare(owner)` rather than to the value that
+    /// was read, once the readers have drained.
+    #[inline]
+    pub(crate) fn with_unshare(
+        mut self,
+        owner: *const (),
+        unshare: unsafe fn(*const ()) -> *mut C,
+    ) -> Self {
+        self.unshare = Some((owner, unshare));
+        self
+    }
+
+    /// Waits for the plain readers to drain and returns a write guard, without
+    /// letting any other writer in between.
+    pub fn upgrade(self) -> WriteGuard<'a, C> {
+        let mut value = self.value;
+        let w = self._u.upgrade();
+        if let Some((owner, unshare)) = self.unshare {
+            // SAFETY: the write lock is held and readers have drained.
+            value = unsafe { NonNull::new_unchecked(unshare(owner)) };
+        }
+        // SAFETY: the write lock is held and readers have drained.
+        WriteGuard::new(w, unsafe { value.as_mut() })
+    }
+}
+
+impl<'a, C> Deref for UpgradableReadGuard<'a, C> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the guard holds the writer lock, so nothing mutates `value`.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<'a, C: Debug> Debug for UpgradableReadGuard<'a, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
     }
 }
 
 /// Internal RAII token for the write path: holds the writer mutex and keeps
 /// the `writing` flag set until dropped, so readers know a writer is active.
 pub(crate) struct WriteLock<'a> {
-    _lock: MutexGuard<'a, ()>,
-    writing: &'a AtomicBool,
+    raw: &'a RawLock,
 }
 
 impl<'a> WriteLock<'a> {
-    #[inline]
-    pub(crate) fn new(_lock: MutexGuard<'a, ()>, writing: &'a AtomicBool) -> Self {
-        WriteLock { _lock, writing }
+    /// Turns the write lock into a read lock of the calling thread without
+    /// letting another writer in between.
+    pub(crate) fn downgrade(self) -> ReadLock<'a> {
+        let raw = self.raw;
+        // Readers have drained and stay out while `writing` is set, so taking
+        // a slot here cannot race with a writer's drain check.
+        let count = reader_count_for(raw.id, &raw.registry);
+        count.fetch_add(1, Ordering::SeqCst);
+        raw.stats.read();
+        drop(self);
+        ReadLock { raw, count }
     }
 }
 
 impl<'a> Drop for WriteLock<'a> {
     fn drop(&mut self) {
-        self.writing.store(false, Ordering::SeqCst);
+        self.raw.writing.store(false, Ordering::SeqCst);
+        // SAFETY: a `WriteLock` is only created right after `write` has been
+        // locked by the current owner, and it unlocks it exactly once.
+        unsafe { self.raw.write.unlock() };
+        self.raw.waiters.notify_all();
     }
 }
 
 /// An RAII write guard returned by the `get_mut` methods of the synchronous
-/// containers (`SyncHashMap`, `SyncBtreeMap`, `SyncVec`, `SyncIndexMap`).
+/// containers (`SyncHashMap`, `SyncBtreeMap`, `SyncVec`, `SyncIndexMap`) and
+/// by [`SyncCell::write`].
 ///
 /// It holds the writer lock (and the `writing` flag) until dropped, so no
 /// reader or writer can touch the value while the guard is alive.
 pub struct WriteGuard<'a, V> {
     _w: WriteLock<'a>,
-    value: &'a mut V,
+    value: NonNull<V>,
+    _marker: PhantomData<&'a mut V>,
 }
 
+// SAFETY: same auto traits as the `&'a mut V` the guard stands for.
+unsafe impl<'a, V: Send> Send for WriteGuard<'a, V> {}
+unsafe impl<'a, V: Sync> Sync for WriteGuard<'a, V> {}
+
 impl<'a, V> WriteGuard<'a, V> {
     #[inline]
     pub(crate) fn new(_w: WriteLock<'a>, value: &'a mut V) -> Self {
-        WriteGuard { _w, value }
+        WriteGuard {
+            _w,
+            value: NonNull::from(value),
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -237,26 +1135,28 @@ impl<'a, V> Deref for WriteGuard<'a, V> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        &*self.value
+        // SAFETY: the guard holds the writer lock and readers have drained.
+        unsafe { self.value.as_ref() }
     }
 }
 
 impl<'a, V> DerefMut for WriteGuard<'a, V> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut *self.value
+        // SAFETY: as for `deref`, and `&mut self` makes the borrow unique.
+        unsafe { self.value.as_mut() }
     }
 }
 
 impl<'a, V: Debug> Debug for WriteGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(&*self.value, f)
+        Debug::fmt(&**self, f)
     }
 }
 
 impl<'a, V: Display> Display for WriteGuard<'a, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&*self.value, f)
+        Display::fmt(&**self, f)
     }
 }
 
@@ -268,9 +1168,66 @@ impl<'a, V: PartialEq> PartialEq for WriteGuard<'a, V> {
 
 impl<'a, V: Eq> Eq for WriteGuard<'a, V> {}
 
+pub use cell::SyncCell;
+pub use deadlock::{deadlock_policy, set_deadlock_policy, DeadlockPolicy};
 pub use duration::*;
+pub use left_right::{LeftRightGuard, LeftRightIter};
 pub use map_btree::SyncBtreeMap;
 pub use map_hash::SyncHashMap;
 pub use map_index::SyncIndexMap;
+pub use map_left_right::{LeftRightBatch, LeftRightBtreeMap, LeftRightHashMap};
+pub use map_sharded::ShardedSyncHashMap;
+pub use ring_buffer::{Overflow, SyncRingBuffer};
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+pub use tx::Transaction;
 pub use vec::*;
+pub use vec_deque::SyncVecDeque;
 pub use wg::*;
+
+#[cfg(test)]
+mod test {
+    use super::{RawLock, PURGE_MIN, SLOTS};
+    use std::sync::Arc;
+
+    // Reader slots of exited threads are recycled: churning short-lived
+    // threads must not grow the registry beyond the peak concurrency.
+    #[test]
+    fn test_registry_reclaims_exited_threads() {
+        let raw = Arc::new(RawLock::new("RawLock"));
+        drop(raw.begin_read());
+        for _ in 0..50 {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let raw = raw.clone();
+                    std::thread::spawn(move || drop(raw.begin_read()))
+                })
+                .collect();
+            for h in handles {
+                h.join().unwrap();
+            }
+        }
+        assert!(raw.registry.len() <= 5, "{}", raw.registry.len());
+        drop(raw.begin_write());
+    }
+
+    // A thread that reads many short-lived containers must not keep an entry
+    // for every one of them.
+    #[test]
+    fn test_slots_purge_dropped_containers() {
+        std::thread::spawn(|| {
+            for _ in 0..10_000 {
+                let raw = RawLock::new("RawLock");
+                drop(raw.begin_read());
+            }
+            let live = RawLock::new("RawLock");
+            drop(live.begin_read());
+            let n = SLOTS.with(|slots| slots.all.borrow().len());
+            assert!(n <= 2 * PURGE_MIN, "{}", n);
+            // the entry of a live container survives purges
+            assert!(SLOTS.with(|slots| slots.all.borrow().contains_key(&live.id)));
+        })
+        .join()
+        .unwrap();
+    }
+}
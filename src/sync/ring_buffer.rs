@@ -0,0 +1,286 @@
+use serde::{Serialize, Serializer};
+use std::collections::vec_deque::Iter as DequeIter;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::{ReadGuard, ReadLock, ReadMapGuard, SyncCell};
+
+/// What [`SyncRingBuffer::push`] does when the buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the oldest element to make room (the default).
+    #[default]
+    EvictOldest,
+    /// Keep the buffer as it is and drop the new element.
+    Reject,
+}
+
+/// Read guard returned by [`SyncRingBuffer::get`].
+pub type RingBufferGet<'a, V> = ReadGuard<'a, V>;
+
+/// Read iterator returned by [`SyncRingBuffer::iter`], oldest element first.
+///
+/// Like [`VecIter`](super::VecIter) it holds the reading thread's slot and is
+/// not `Send`.
+pub struct RingBufferIter<'a, V> {
+    _r: ReadLock<'a>,
+    inner: DequeIter<'a, V>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, V> Iterator for RingBufferIter<'a, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, V> DoubleEndedIterator for RingBufferIter<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+/// A thread-safe buffer that holds at most `max_len` elements, e.g. the last
+/// N requests of a debug log.
+///
+/// When the buffer is full, [`push`](SyncRingBuffer::push) evicts the oldest
+/// element or rejects the new one, depending on its [`Overflow`] policy, so
+/// memory stays bounded. Reads and writes follow the same protocol as
+/// [`SyncVec`](super::SyncVec): `get`/`iter`/`len` only register a reader
+/// slot, and writers wait for readers to drain.
+///
+/// # Deadlock note
+/// As for [`SyncVec`](super::SyncVec): drop read guards before writing from
+/// the same thread.
+///
+/// # Examples
+///
+/// ```
+/// use dark_std::sync::SyncRingBuffer;
+///
+/// let last = SyncRingBuffer::new(2);
+/// last.push("a");
+/// last.push("b");
+/// assert_eq!(last.push("c"), Some("a"));
+/// assert_eq!(last.to_vec(), ["b", "c"]);
+/// ```
+pub struct SyncRingBuffer<V> {
+    dirty: SyncCell<VecDeque<V>>,
+    max_len: usize,
+    overflow: Overflow,
+}
+
+impl<V> SyncRingBuffer<V> {
+    pub fn new_arc(max_len: usize) -> Arc<Self> {
+        Arc::new(Self::new(max_len))
+    }
+
+    /// A buffer of at most `max_len` elements that evicts the oldest one when
+    /// full.
+    pub fn new(max_len: usize) -> Self {
+        Self::with_overflow(max_len, Overflow::EvictOldest)
+    }
+
+    pub fn with_overflow(max_len: usize, overflow: Overflow) -> Self {
+        Self {
+            dirty: SyncCell::named(VecDeque::new(), "SyncRingBuffer"),
+            max_len,
+            overflow,
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Appends `v` as the newest element. Returns the element that did not
+    /// fit: the evicted oldest one under [`Overflow::EvictOldest`], or `v`
+    /// itself under [`Overflow::Reject`]; `None` if there was room.
+    pub fn push(&self, v: V) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        if m.len() < self.max_len {
+            m.push_back(v);
+            return None;
+        }
+        match self.overflow {
+            Overflow::Reject => Some(v),
+            Overflow::EvictOldest if self.max_len == 0 => Some(v),
+            Overflow::EvictOldest => {
+                let old = m.pop_front();
+                m.push_back(v);
+                old
+            }
+        }
+    }
+
+    /// Pushes every element of `iter` under one writer lock and returns the
+    /// elements that did not fit, in order.
+    pub fn pushes<I>(&self, iter: I) -> Vec<V>
+    where
+        I: IntoIterator<Item = V>,
+    {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        let mut dropped = Vec::new();
+        for v in iter {
+            if m.len() < self.max_len {
+                m.push_back(v);
+            } else if self.overflow == Overflow::Reject || self.max_len == 0 {
+                dropped.push(v);
+            } else {
+                dropped.extend(m.pop_front());
+                m.push_back(v);
+            }
+        }
+        dropped
+    }
+
+    /// Removes and returns the oldest element.
+    pub fn pop_front(&self) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop_front()
+    }
+
+    /// Removes and returns the newest element.
+    pub fn pop_back(&self) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop_back()
+    }
+
+    pub fn clear(&self) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.max_len
+    }
+
+    /// Returns a read-guarded reference to the element at `index`, counting
+    /// from the oldest one.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<RingBufferGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(index).map(|v| ReadGuard::new(r, v))
+    }
+
+    /// The oldest element.
+    pub fn front(&self) -> Option<RingBufferGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.front().map(|v| ReadGuard::new(r, v))
+    }
+
+    /// The newest element.
+    pub fn back(&self) -> Option<RingBufferGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.back().map(|v| ReadGuard::new(r, v))
+    }
+
+    pub fn iter(&self) -> RingBufferIter<'_, V> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        RingBufferIter {
+            _r: r,
+            inner: m.iter(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The elements, oldest first.
+    pub fn to_vec(&self) -> Vec<V>
+    where
+        V: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+
+    pub fn dirty_ref(&self) -> ReadMapGuard<'_, VecDeque<V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        ReadMapGuard::new(r, m)
+    }
+
+    /// Contention counters of this buffer, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    pub fn into_inner(self) -> VecDeque<V> {
+        self.dirty.into_inner()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a SyncRingBuffer<V> {
+    type Item = &'a V;
+    type IntoIter = RingBufferIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> Serialize for SyncRingBuffer<V>
+where
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.dirty_ref().serialize(serializer)
+    }
+}
+
+impl<V> Debug for SyncRingBuffer<V>
+where
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.dirty_ref(), f)
+    }
+}
+
+impl<V> Display for SyncRingBuffer<V>
+where
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.dirty_ref(), f)
+    }
+}
+
+impl<V: Clone> Clone for SyncRingBuffer<V> {
+    fn clone(&self) -> Self {
+        Self {
+            dirty: SyncCell::named(self.dirty_ref().clone(), "SyncRingBuffer"),
+            max_len: self.max_len,
+            overflow: self.overflow,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Contention counters of a container (cargo feature `stats`).
+//!
+//! Without the feature, [`Counters`] is zero-sized and every hook compiles to
+//! nothing, so the read fast path is unchanged.
+
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "stats")]
+use std::time::{Duration, Instant};
+
+/// A snapshot of a container's contention counters, returned by the `stats`
+/// methods of the Sync containers.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Read locks taken (`get`, `iter`, `dirty_ref`, `len`...).
+    pub reads: u64,
+    /// Write locks taken (`insert`, `remove`, `get_mut`, batches...).
+    pub writes: u64,
+    /// Total time writers spent waiting for the writer lock and the readers.
+    pub write_wait_total: Duration,
+    /// Longest single writer wait.
+    pub write_wait_max: Duration,
+    /// Times a reader backed off because a writer was active.
+    pub read_retries: u64,
+    /// Reader slots currently held by at least one read guard.
+    pub active_reader_slots: usize,
+    /// Reader slots allocated in the registry (live and free).
+    pub registry_slots: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "stats")]
+    reads: AtomicU64,
+    #[cfg(feature = "stats")]
+    writes: AtomicU64,
+    #[cfg(feature = "stats")]
+    write_wait_ns: AtomicU64,
+    #[cfg(feature = "stats")]
+    write_wait_max_ns: AtomicU64,
+    #[cfg(feature = "stats")]
+    read_retries: AtomicU64,
+}
+
+/// When a writer started waiting; zero-sized without the feature.
+pub(crate) struct WaitStart(#[cfg(feature = "stats")] Instant);
+
+impl Counters {
+    #[inline(always)]
+    pub(crate) fn read(&self) {
+        #[cfg(feature = "stats")]
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub(crate) fn read_retry(&self) {
+        #[cfg(feature = "stats")]
+        self.read_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub(crate) fn write_start(&self) -> WaitStart {
+        WaitStart(
+            #[cfg(feature = "stats")]
+            Instant::now(),
+        )
+    }
+
+    #[inline(always)]
+    pub(crate) fn write(&self, _start: WaitStart) {
+        #[cfg(feature = "stats")]
+        {
+            let ns = _start.0.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.write_wait_ns.fetch_add(ns, Ordering::Relaxed);
+            self.write_wait_max_ns.fetch_max(ns, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn snapshot(&self, active_reader_slots: usize, registry_slots: usize) -> Stats {
+        Stats {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            write_wait_total: Duration::from_nanos(self.write_wait_ns.load(Ordering::Relaxed)),
+            write_wait_max: Duration::from_nanos(self.write_wait_max_ns.load(Ordering::Relaxed)),
+            read_retries: self.read_retries.load(Ordering::Relaxed),
+            active_reader_slots,
+            registry_slots,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::ops::Deref;
+
+/// One recorded undo step.
+type Undo<'a, C> = Box<dyn FnOnce(&mut C) + 'a>;
+
+/// A batch of changes made under one writer lock by the `transact` methods
+/// of the Sync containers.
+///
+/// Every change made through the transaction records how to undo itself. If
+/// the closure passed to `transact` returns `Err` or panics, the changes are
+/// undone in reverse order before the writer lock is released, so readers see
+/// the whole batch or none of it. Reads go through `Deref` to the container.
+pub struct Transaction<'a, C> {
+    data: &'a mut C,
+    undo: Vec<Undo<'a, C>>,
+}
+
+impl<'a, C> Transaction<'a, C> {
+    pub(crate) fn new(data: &'a mut C) -> Self {
+        Transaction {
+            data,
+            undo: Vec::new(),
+        }
+    }
+
+    /// Keeps the changes: nothing is undone when the transaction drops.
+    pub(crate) fn commit(mut self) {
+        self.undo.clear();
+    }
+
+    /// The container, for a change that records its own undo step.
+    #[inline]
+    pub(crate) fn data(&mut self) -> &mut C {
+        self.data
+    }
+
+    #[inline]
+    pub(crate) fn on_rollback<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut C) + 'a,
+    {
+        self.undo.push(Box::new(f));
+    }
+}
+
+impl<'a, C> Deref for Transaction<'a, C> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<'a, C> Drop for Transaction<'a, C> {
+    fn drop(&mut self) {
+        while let Some(undo) = self.undo.pop() {
+            undo(self.data);
+        }
+    }
+}
@@ -1,38 +1,62 @@
-use parking_lot::Mutex;
 use serde::{Deserializer, Serialize, Serializer};
-use std::cell::UnsafeCell;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::{Deref, DerefMut, Index};
+use std::marker::PhantomData;
+use std::ops::{Bound, Deref, DerefMut, Index, RangeBounds};
 use std::slice::{Iter as SliceIter, IterMut as SliceIterMut};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::vec::IntoIter;
 
-use super::{ReadGuard, ReadMapGuard, WriteGuard, WriteLock};
+use crate::err;
+use crate::errors::Error;
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::{
+    OwnedIter, OwnedReadGuard, ReadGuard, ReadLock, ReadMapGuard, SendReadGuard, SendReadMapGuard,
+    SyncCell, Transaction, WriteGuard, WriteLock,
+};
 
 /// Read guard returned by [`SyncVec::get`].
 pub type VecGet<'a, V> = ReadGuard<'a, V>;
 
+/// Read guard returned by [`SyncVec::as_slice`] and [`SyncVec::get_range`].
+pub type VecSlice<'a, V> = ReadMapGuard<'a, [V]>;
+
 /// Write guard returned by [`SyncVec::get_mut`].
 pub type VecRefMut<'a, V> = WriteGuard<'a, V>;
 
 /// Read iterator returned by [`SyncVec::iter`].
 ///
-/// The iterator is `Send` (when `V: Sync`) and may be moved between threads:
-/// the reader counter is a shared atomic owned by the container, so releasing
-/// it from another thread (on drop) is safe.
+/// Like [`VecGet`] it holds the reading thread's slot and is not `Send`; use
+/// [`SyncVec::iter_send`] to move the iterator to another thread.
 pub struct VecIter<'a, V> {
-    count: &'a AtomicUsize,
+    _r: ReadLock<'a>,
     inner: SliceIter<'a, V>,
+    _not_send: PhantomData<*const ()>,
 }
 
-impl<'a, V> Drop for VecIter<'a, V> {
-    fn drop(&mut self) {
-        self.count.fetch_sub(1, Ordering::Release);
+impl<'a, V> Iterator for VecIter<'a, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
     }
 }
 
-impl<'a, V> Iterator for VecIter<'a, V> {
+/// Read iterator returned by [`SyncVec::iter_send`].
+///
+/// The iterator is `Send` (when `V: Sync`) and may be moved between threads:
+/// the reader counter is a shared atomic owned by the container, so releasing
+/// it from another thread (on drop) is safe.
+pub struct VecIterSend<'a, V> {
+    _r: ReadLock<'a>,
+    inner: SliceIter<'a, V>,
+}
+
+impl<'a, V> Iterator for VecIterSend<'a, V> {
     type Item = &'a V;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -40,6 +64,20 @@ impl<'a, V> Iterator for VecIter<'a, V> {
     }
 }
 
+/// Owned read guard returned by [`SyncVec::get_owned`].
+pub type VecGetOwned<V> = OwnedReadGuard<SyncVec<V>, V>;
+
+/// Owned iterator returned by [`SyncVec::iter_owned`].
+pub type VecIterOwned<V> = OwnedIter<SyncVec<V>, SliceIter<'static, V>>;
+
+impl<V: Clone> Iterator for VecIterOwned<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner().next().cloned()
+    }
+}
+
 /// Write iterator returned by [`SyncVec::iter_mut`].
 pub struct VecIterMut<'a, V> {
     _w: WriteLock<'a>,
@@ -82,129 +120,103 @@ impl<'a, V> DerefMut for VecIterMut<'a, V> {
 /// method while a read/write guard is alive in the same scope: drop the guard
 /// first (e.g. `drop(g)` before `push`/`remove`/`get_mut`), otherwise the
 /// writer waits for its own guard and deadlocks.
+/// Debug builds detect this and panic with a message naming the container;
+/// see [`DeadlockPolicy`](crate::sync::DeadlockPolicy) to configure it.
 pub struct SyncVec<V> {
-    dirty: UnsafeCell<Vec<V>>,
-    write: Mutex<()>,
-    id: usize,
-    writing: AtomicBool,
-    registry: Mutex<Vec<std::boxed::Box<AtomicUsize>>>,
+    dirty: SyncCell<Vec<V>>,
 }
 
-// SAFETY: all writers hold `write` and wait for `readers` to drain before
-// touching `dirty`; readers either see a consistent snapshot or retry while a
-// writer is active, so concurrent access to `dirty` is race-free.
+// SAFETY: `dirty` is a `SyncCell`: all writers hold its writer lock and wait
+// for readers to drain before touching the data; readers either see a
+// consistent snapshot or retry while a writer is active, so concurrent access
+// to `dirty` is race-free.
 unsafe impl<V: Send> Send for SyncVec<V> {}
 unsafe impl<V: Sync> Sync for SyncVec<V> {}
 
 impl<V> SyncVec<V> {
-    #[inline]
-    fn begin_read(&self) -> &AtomicUsize {
-        // The counter lives in thread-local storage: concurrent readers only
-        // touch their own cache line and never contend with each other. SeqCst
-        // closes the store-buffering window with the writer's all-zero scan.
-        let count = super::reader_count_for(self.id, &self.registry);
-        loop {
-            count.fetch_add(1, Ordering::SeqCst);
-            if !self.writing.load(Ordering::SeqCst) {
-                return count;
-            }
-            count.fetch_sub(1, Ordering::SeqCst);
-            std::thread::yield_now();
-        }
-    }
-
-    #[inline]
-    fn begin_write(&self) -> WriteLock<'_> {
-        let lock = self.write.lock();
-        self.writing.store(true, Ordering::SeqCst);
-        loop {
-            let registry = self.registry.lock();
-            let all_zero = registry.iter().all(|c| c.load(Ordering::SeqCst) == 0);
-            if all_zero {
-                break;
-            }
-            drop(registry);
-            std::thread::yield_now();
-        }
-        WriteLock::new(lock, &self.writing)
-    }
-
     pub fn new_arc() -> Arc<Self> {
         Arc::new(Self::new())
     }
 
     pub fn new() -> Self {
         Self {
-            dirty: UnsafeCell::new(Vec::new()),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(Vec::new(), "SyncVec"),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            dirty: UnsafeCell::new(Vec::with_capacity(capacity)),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(Vec::with_capacity(capacity), "SyncVec"),
         }
     }
 
     pub fn with_vec(vec: Vec<V>) -> Self {
         Self {
-            dirty: UnsafeCell::new(vec),
-            write: Mutex::new(()),
-            id: super::CONTAINER_ID.fetch_add(1, Ordering::Relaxed),
-            writing: AtomicBool::new(false),
-            registry: Mutex::new(Vec::new()),
+            dirty: SyncCell::named(vec, "SyncVec"),
         }
     }
 
+    /// Inserts `v` at `index`, shifting the following items up. Always
+    /// returns `None`.
+    ///
+    /// Panics if `index` is greater than the length, like `Vec::insert`; see
+    /// [`SyncVec::try_insert`] to get an error instead.
     pub fn insert(&self, index: usize, v: V) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.insert(index, v);
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.insert(index, v);
         None
     }
 
+    /// Replaces the item at `index` and returns the old one, so the result is
+    /// always `Some` when it returns.
+    ///
+    /// Panics if `index` is out of bounds, like indexing a `Vec`; see
+    /// [`SyncVec::try_set`] to get an error instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncVec;
+    ///
+    /// let v = SyncVec::from(vec![1, 2]);
+    /// assert_eq!(v.set(1, 3), Some(2));
+    /// assert_eq!(v.into_inner(), vec![1, 3]);
+    /// ```
     pub fn set(&self, index: usize, v: V) -> Option<V> {
-        let _w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
-        m[index] = v;
-        None
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        Some(std::mem::replace(&mut m[index], v))
     }
 
     pub fn push(&self, v: V) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.push(v);
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.push(v);
         None
     }
 
     pub fn pushes(&self, arr: Vec<V>) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.extend(arr);
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.extend(arr);
         None
     }
 
     pub fn push_mut(&mut self, v: V) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.push(v);
+        self.dirty.get_mut().push(v);
         None
     }
 
     pub fn pop(&self) -> Option<V> {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.pop()
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop()
     }
 
     pub fn pop_mut(&mut self) -> Option<V> {
-        unsafe { &mut *self.dirty.get() }.pop()
+        self.dirty.get_mut().pop()
     }
 
     pub fn remove(&self, index: usize) -> Option<V> {
-        let _w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
         if m.len() > index {
             Some(m.remove(index))
         } else {
@@ -213,7 +225,7 @@ impl<V> SyncVec<V> {
     }
 
     pub fn remove_mut(&mut self, index: usize) -> Option<V> {
-        let m = unsafe { &mut *self.dirty.get() };
+        let m = self.dirty.get_mut();
         if m.len() > index {
             Some(m.remove(index))
         } else {
@@ -222,27 +234,23 @@ impl<V> SyncVec<V> {
     }
 
     pub fn len(&self) -> usize {
-        let count = self.begin_read();
-        let n = unsafe { &*self.dirty.get() }.len();
-        count.fetch_sub(1, Ordering::Release);
-        n
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.is_empty();
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.is_empty()
     }
 
     pub fn clear(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.clear();
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
     }
 
     pub fn shrink_to_fit(&self) {
-        let _w = self.begin_write();
-        unsafe { &mut *self.dirty.get() }.shrink_to_fit();
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shrink_to_fit();
     }
 
     pub fn from(vec: Vec<V>) -> Self {
@@ -256,15 +264,17 @@ impl<V> SyncVec<V> {
     /// the returned guard to be dropped before mutating the vector.
     #[inline]
     pub fn get(&self, index: usize) -> Option<VecGet<'_, V>> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        match m.get(index) {
-            Some(v) => Some(ReadGuard::new(count, v)),
-            None => {
-                count.fetch_sub(1, Ordering::Release);
-                None
-            }
-        }
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(index).map(|v| ReadGuard::new(r, v))
+    }
+
+    /// `Send` version of [`SyncVec::get`]: the guard can be held across an
+    /// `.await` in a multi-threaded runtime and dropped on any thread.
+    pub fn get_send(&self, index: usize) -> Option<SendReadGuard<'_, V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(index).map(|v| SendReadGuard::new(r, v))
     }
 
     /// # Safety
@@ -273,7 +283,7 @@ impl<V> SyncVec<V> {
     /// pre-0.2.17 API).
     #[inline]
     pub unsafe fn get_uncheck(&self, index: usize) -> &V {
-        unsafe { (&*self.dirty.get()).get_unchecked(index) }
+        unsafe { (&*self.dirty.data_ptr()).get_unchecked(index) }
     }
 
     /// Returns a write-guarded mutable reference to the value at `index`.
@@ -284,51 +294,520 @@ impl<V> SyncVec<V> {
     /// before calling another method from the same scope.
     #[inline]
     pub fn get_mut(&self, index: usize) -> Option<VecRefMut<'_, V>> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
         match m.get_mut(index) {
             Some(v) => Some(WriteGuard::new(w, v)),
             None => None,
         }
     }
 
+    /// Async version of [`SyncVec::insert`]: awaits the writer lock and the
+    /// reader drain instead of blocking the OS thread.
+    pub async fn insert_async(&self, index: usize, v: V) -> Option<V> {
+        let _w = self.dirty.begin_write_async().await;
+        unsafe { &mut *self.dirty.data_ptr() }.insert(index, v);
+        None
+    }
+
+    /// Async version of [`SyncVec::push`].
+    pub async fn push_async(&self, v: V) -> Option<V> {
+        let _w = self.dirty.begin_write_async().await;
+        unsafe { &mut *self.dirty.data_ptr() }.push(v);
+        None
+    }
+
+    /// Async version of [`SyncVec::remove`].
+    pub async fn remove_async(&self, index: usize) -> Option<V> {
+        let _w = self.dirty.begin_write_async().await;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        if m.len() > index {
+            Some(m.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Async version of [`SyncVec::get_mut`].
+    pub async fn get_mut_async(&self, index: usize) -> Option<VecRefMut<'_, V>> {
+        let w = self.dirty.begin_write_async().await;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(index) {
+            Some(v) => Some(WriteGuard::new(w, v)),
+            None => None,
+        }
+    }
+
+    /// Runs `f` with exclusive access to the whole vector: one writer lock and
+    /// one reader drain for the whole batch, and readers see all of its
+    /// changes or none of them.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Vec<V>) -> R,
+    {
+        let _w = self.dirty.begin_write();
+        f(unsafe { &mut *self.dirty.data_ptr() })
+    }
+
+    /// Like [`SyncVec::write`], but every change made through the
+    /// [`Transaction`] is rolled back if `f` returns `Err` or panics.
+    pub fn transact<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        V: Clone,
+        F: FnOnce(&mut Transaction<'_, Vec<V>>) -> Result<R, Error>,
+    {
+        let _w = self.dirty.begin_write();
+        let mut tx = Transaction::new(unsafe { &mut *self.dirty.data_ptr() });
+        let r = f(&mut tx)?;
+        tx.commit();
+        Ok(r)
+    }
+
+    /// Runs `f` with exclusive access to the whole vector, awaiting the
+    /// writer lock and the reader drain instead of blocking the OS thread.
+    pub async fn write_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Vec<V>) -> R,
+    {
+        let _w = self.dirty.begin_write_async().await;
+        f(unsafe { &mut *self.dirty.data_ptr() })
+    }
+
+    /// Runs `f` with shared access to the whole vector, awaiting the end of an
+    /// active write instead of yielding the OS thread.
+    pub async fn read_async<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Vec<V>) -> R,
+    {
+        let _r = self.dirty.begin_read_async().await;
+        f(unsafe { &*self.dirty.data_ptr() })
+    }
+
+    /// Bounded version of [`SyncVec::push`]: returns a `Timeout` error if the
+    /// write cannot start within `timeout` (e.g. a leaked `ReadGuard` keeps
+    /// readers from draining). Blocked readers resume either way.
+    pub fn push_timeout(&self, v: V, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_write_timeout(timeout)?;
+        unsafe { &mut *self.dirty.data_ptr() }.push(v);
+        Ok(None)
+    }
+
+    /// Bounded version of [`SyncVec::insert`]; also returns an `OutOfRange`
+    /// error instead of panicking if `index` is greater than the length.
+    pub fn insert_timeout(
+        &self,
+        index: usize,
+        v: V,
+        timeout: Duration,
+    ) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_write_timeout(timeout)?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        check_insert_index(index, m.len())?;
+        m.insert(index, v);
+        Ok(None)
+    }
+
+    /// Bounded version of [`SyncVec::remove`].
+    pub fn remove_timeout(&self, index: usize, timeout: Duration) -> Result<Option<V>, Error> {
+        let _w = self.dirty.begin_write_timeout(timeout)?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        if m.len() > index {
+            Ok(Some(m.remove(index)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Bounded version of [`SyncVec::get_mut`].
+    pub fn get_mut_timeout(
+        &self,
+        index: usize,
+        timeout: Duration,
+    ) -> Result<Option<VecRefMut<'_, V>>, Error> {
+        let w = self.dirty.begin_write_timeout(timeout)?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(index) {
+            Some(v) => Ok(Some(WriteGuard::new(w, v))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the default write timeout, see
+    /// [`SyncVec::set_default_write_timeout`].
+    pub fn default_write_timeout(&self) -> Option<Duration> {
+        self.dirty.default_write_timeout()
+    }
+
+    /// Makes the `try_*` writes wait up to `timeout` for the writer lock and
+    /// the reader drain, then return a `Timeout` error, instead of failing
+    /// at once with `WouldBlock`. Blocking writes (`push`, `insert`,
+    /// `remove`, `get_mut`...) are not affected and still wait forever.
+    /// `None` (the default) turns it off.
+    pub fn set_default_write_timeout(&self, timeout: Option<Duration>) {
+        self.dirty.set_default_write_timeout(timeout)
+    }
+
+    /// Contention counters of this vector, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    /// Non-blocking version of [`SyncVec::push`]: returns a `WouldBlock`
+    /// error instead of waiting when another writer holds the lock or readers
+    /// are active, so the caller can fall back, queue the write or retry.
+    pub fn try_push(&self, v: V) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.push(v);
+        Ok(None)
+    }
+
+    /// Non-blocking version of [`SyncVec::pushes`].
+    pub fn try_pushes(&self, arr: Vec<V>) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.extend(arr);
+        Ok(None)
+    }
+
+    /// Non-blocking version of [`SyncVec::insert`] that returns an
+    /// `OutOfRange` error if `index` is greater than the length.
+    pub fn try_insert(&self, index: usize, v: V) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        check_insert_index(index, m.len())?;
+        m.insert(index, v);
+        Ok(None)
+    }
+
+    /// Non-blocking version of [`SyncVec::set`] that returns an `OutOfRange`
+    /// error if `index` is out of bounds. As with `set`, `Ok` always holds
+    /// `Some` of the replaced item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncVec;
+    ///
+    /// let v = SyncVec::from(vec![1]);
+    /// assert_eq!(v.try_set(0, 2).unwrap(), Some(1));
+    /// assert!(v.try_set(1, 3).is_err());
+    /// ```
+    pub fn try_set(&self, index: usize, v: V) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        let len = m.len();
+        match m.get_mut(index) {
+            Some(old) => Ok(Some(std::mem::replace(old, v))),
+            None => Err(out_of_range(index, len)),
+        }
+    }
+
+    /// Non-blocking version of [`SyncVec::pop`].
+    pub fn try_pop(&self) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.pop())
+    }
+
+    /// Non-blocking version of [`SyncVec::remove`].
+    pub fn try_remove(&self, index: usize) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        if m.len() > index {
+            Ok(Some(m.remove(index)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Non-blocking version of [`SyncVec::get_mut`].
+    pub fn try_get_mut(&self, index: usize) -> Result<Option<VecRefMut<'_, V>>, Error> {
+        let w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        match m.get_mut(index) {
+            Some(v) => Ok(Some(WriteGuard::new(w, v))),
+            None => Ok(None),
+        }
+    }
+
+    /// Non-blocking version of [`SyncVec::clear`].
+    pub fn try_clear(&self) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncVec::iter_mut`].
+    pub fn try_iter_mut(&self) -> Result<VecIterMut<'_, V>, Error> {
+        let w = self.dirty.try_begin_write()?;
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        Ok(VecIterMut {
+            _w: w,
+            inner: m.iter_mut(),
+        })
+    }
+
     #[inline]
     pub fn contains(&self, x: &V) -> bool
     where
         V: PartialEq,
     {
-        let count = self.begin_read();
-        let b = unsafe { &*self.dirty.get() }.contains(x);
-        count.fetch_sub(1, Ordering::Release);
-        b
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.contains(x)
     }
 
     pub fn iter(&self) -> VecIter<'_, V> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
         VecIter {
-            count,
+            _r: r,
+            inner: m.iter(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// `Send` version of [`SyncVec::iter`]: the iterator pins the shared
+    /// counter instead of the calling thread's slot, so it can be moved to
+    /// another thread or held across an `.await`.
+    pub fn iter_send(&self) -> VecIterSend<'_, V> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        VecIterSend {
+            _r: r,
             inner: m.iter(),
         }
     }
 
     pub fn iter_mut(&self) -> VecIterMut<'_, V> {
-        let w = self.begin_write();
-        let m = unsafe { &mut *self.dirty.get() };
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
         VecIterMut {
             _w: w,
             inner: m.iter_mut(),
         }
     }
 
-    pub fn into_iter(self) -> IntoIter<V> {
-        self.into_inner().into_iter()
+    pub fn first(&self) -> Option<VecGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.first().map(|v| ReadGuard::new(r, v))
+    }
+
+    pub fn last(&self) -> Option<VecGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.last().map(|v| ReadGuard::new(r, v))
+    }
+
+    /// The whole vector as a read-guarded slice.
+    pub fn as_slice(&self) -> VecSlice<'_, V> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        ReadMapGuard::new(r, m.as_slice())
+    }
+
+    /// The items in `range` as a read-guarded slice, or `None` if the range is
+    /// out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dark_std::sync::SyncVec;
+    ///
+    /// let v = SyncVec::from(vec![1, 2, 3, 4]);
+    /// assert_eq!(*v.get_range(1..3).unwrap(), [2, 3]);
+    /// assert!(v.get_range(3..5).is_none());
+    /// ```
+    pub fn get_range<R>(&self, range: R) -> Option<VecSlice<'_, V>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        let bounds: (Bound<usize>, Bound<usize>) =
+            (range.start_bound().cloned(), range.end_bound().cloned());
+        m.get(bounds).map(|s| ReadMapGuard::new(r, s))
+    }
+
+    /// See [`slice::binary_search`]; the vector must be sorted.
+    pub fn binary_search(&self, x: &V) -> Result<usize, usize>
+    where
+        V: Ord,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.binary_search(x)
+    }
+
+    /// See [`slice::binary_search_by_key`].
+    pub fn binary_search_by_key<B, F>(&self, b: &B, f: F) -> Result<usize, usize>
+    where
+        B: Ord,
+        F: FnMut(&V) -> B,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.binary_search_by_key(b, f)
+    }
+
+    pub fn sort(&self)
+    where
+        V: Ord,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort();
+    }
+
+    pub fn sort_by<F>(&self, compare: F)
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort_by(compare);
+    }
+
+    pub fn sort_by_key<K, F>(&self, f: F)
+    where
+        K: Ord,
+        F: FnMut(&V) -> K,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort_by_key(f);
+    }
+
+    pub fn sort_unstable(&self)
+    where
+        V: Ord,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.sort_unstable();
+    }
+
+    /// Removes consecutive repeated items.
+    pub fn dedup(&self)
+    where
+        V: PartialEq,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.dedup();
+    }
+
+    /// Keeps only the items for which `f` returns `true`, under one writer
+    /// lock.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&V) -> bool,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.retain(f);
+    }
+
+    pub fn truncate(&self, len: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.truncate(len);
+    }
+
+    /// Swaps the items at `a` and `b`.
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn swap(&self, a: usize, b: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.swap(a, b);
+    }
+
+    /// Moves the items from `at` on into a new vector.
+    ///
+    /// Panics if `at` is greater than the length.
+    pub fn split_off(&self, at: usize) -> Self {
+        let _w = self.dirty.begin_write();
+        Self::with_vec(unsafe { &mut *self.dirty.data_ptr() }.split_off(at))
+    }
+
+    /// Removes the items in `range` and returns them. They are moved out
+    /// under the writer lock, so the lock is released before they are
+    /// iterated.
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R>(&self, range: R) -> IntoIter<V>
+    where
+        R: RangeBounds<usize>,
+    {
+        let _w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        m.drain(range).collect::<Vec<_>>().into_iter()
+    }
+
+    pub fn extend_from_slice(&self, other: &[V])
+    where
+        V: Clone,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.extend_from_slice(other);
+    }
+
+    /// Resizes the vector to `new_len`, filling new slots with clones of
+    /// `value`.
+    pub fn resize(&self, new_len: usize, value: V)
+    where
+        V: Clone,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.resize(new_len, value);
+    }
+
+    /// Rotates the items `mid` places to the left.
+    ///
+    /// Panics if `mid` is greater than the length.
+    pub fn rotate_left(&self, mid: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.rotate_left(mid);
+    }
+
+    /// Rotates the items `k` places to the right.
+    ///
+    /// Panics if `k` is greater than the length.
+    pub fn rotate_right(&self, k: usize) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.rotate_right(k);
     }
 
     pub fn dirty_ref(&self) -> ReadMapGuard<'_, Vec<V>> {
-        let count = self.begin_read();
-        let m = unsafe { &*self.dirty.get() };
-        ReadMapGuard::new(count, m)
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        ReadMapGuard::new(r, m)
+    }
+
+    /// `Send` version of [`SyncVec::dirty_ref`], e.g. to iterate the whole
+    /// container across `.await` points.
+    pub fn dirty_ref_send(&self) -> SendReadMapGuard<'_, Vec<V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        SendReadMapGuard::new(r, m)
+    }
+
+    /// Owned version of [`SyncVec::get`] on an `Arc`-wrapped vector: the guard
+    /// keeps the vector alive instead of borrowing it, so it can be stored,
+    /// returned or moved into a spawned task.
+    pub fn get_owned(self: &Arc<Self>, index: usize) -> Option<VecGetOwned<V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        let v = m.get(index)?;
+        Some(unsafe { OwnedReadGuard::new(self.clone(), r, v) })
+    }
+
+    /// Owned version of [`SyncVec::iter`]: yields clones of the items while
+    /// holding a read lock, see [`OwnedIter`].
+    pub fn iter_owned(self: &Arc<Self>) -> VecIterOwned<V>
+    where
+        V: 'static,
+    {
+        let r = self.dirty.begin_read_send();
+        let m: &'static Vec<V> = unsafe { &*self.dirty.data_ptr() };
+        unsafe { OwnedIter::new(self.clone(), r, m.iter()) }
+    }
+
+    /// Owned version of [`SyncVec::dirty_ref`].
+    pub fn read_owned(self: &Arc<Self>) -> OwnedReadGuard<Self, Vec<V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        unsafe { OwnedReadGuard::new(self.clone(), r, m) }
     }
 
     pub fn into_inner(self) -> Vec<V> {
@@ -336,12 +815,24 @@ impl<V> SyncVec<V> {
     }
 }
 
+fn out_of_range(index: usize, len: usize) -> Error {
+    err!("OutOfRange: index {} is out of bounds (len {})", index, len)
+}
+
+/// `Vec::insert` accepts `index == len`.
+fn check_insert_index(index: usize, len: usize) -> Result<(), Error> {
+    if index > len {
+        return Err(out_of_range(index, len));
+    }
+    Ok(())
+}
+
 impl<V> IntoIterator for SyncVec<V> {
     type Item = V;
     type IntoIter = IntoIter<V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.into_iter()
+        self.into_inner().into_iter()
     }
 }
 
@@ -366,7 +857,7 @@ impl<V> Index<usize> for SyncVec<V> {
     type Output = V;
 
     fn index(&self, index: usize) -> &Self::Output {
-        unsafe { &*self.dirty.get() }
+        unsafe { &*self.dirty.data_ptr() }
             .get(index)
             .expect("index out of bounds")
     }
@@ -433,6 +924,61 @@ impl<V> Default for SyncVec<V> {
     }
 }
 
+/// Changes made inside [`SyncVec::transact`]. Each one records how to undo
+/// itself; reads go through `Deref` to the vector.
+impl<'a, V: Clone> Transaction<'a, Vec<V>> {
+    pub fn push(&mut self, v: V) {
+        self.data().push(v);
+        self.on_rollback(|m| {
+            m.pop();
+        });
+    }
+
+    pub fn pop(&mut self) -> Option<V> {
+        let v = self.data().pop()?;
+        let old = v.clone();
+        self.on_rollback(move |m| m.push(old));
+        Some(v)
+    }
+
+    pub fn insert(&mut self, index: usize, v: V) {
+        self.data().insert(index, v);
+        self.on_rollback(move |m| {
+            m.remove(index);
+        });
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<V> {
+        if index >= self.len() {
+            return None;
+        }
+        let v = self.data().remove(index);
+        let old = v.clone();
+        self.on_rollback(move |m| m.insert(index, old));
+        Some(v)
+    }
+
+    /// Replaces the item at `index` and returns the old one. Panics if `index`
+    /// is out of bounds, like [`SyncVec::set`].
+    pub fn set(&mut self, index: usize, v: V) -> V {
+        let old = std::mem::replace(&mut self.data()[index], v);
+        let undo = old.clone();
+        self.on_rollback(move |m| m[index] = undo);
+        old
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut V> {
+        let old = self.get(index)?.clone();
+        self.on_rollback(move |m| m[index] = old);
+        self.data().get_mut(index)
+    }
+
+    pub fn clear(&mut self) {
+        let old = std::mem::take(self.data());
+        self.on_rollback(move |m| *m = old);
+    }
+}
+
 #[macro_export]
 macro_rules! sync_vec {
     () => (
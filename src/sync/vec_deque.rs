@@ -0,0 +1,417 @@
+use serde::{Deserializer, Serialize, Serializer};
+use std::collections::vec_deque::{IntoIter, Iter as DequeIter, IterMut as DequeIterMut};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::errors::Error;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+use super::{ReadGuard, ReadLock, ReadMapGuard, SendReadMapGuard, SyncCell, WriteGuard, WriteLock};
+
+/// Read guard returned by [`SyncVecDeque::get`], `front` and `back`.
+pub type VecDequeGet<'a, V> = ReadGuard<'a, V>;
+
+/// Write guard returned by [`SyncVecDeque::get_mut`], `front_mut` and
+/// `back_mut`.
+pub type VecDequeRefMut<'a, V> = WriteGuard<'a, V>;
+
+/// Read iterator returned by [`SyncVecDeque::iter`], front to back.
+///
+/// Like [`VecIter`](super::VecIter) it holds the reading thread's slot and is
+/// not `Send`.
+pub struct VecDequeIter<'a, V> {
+    _r: ReadLock<'a>,
+    inner: DequeIter<'a, V>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, V> Iterator for VecDequeIter<'a, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, V> DoubleEndedIterator for VecDequeIter<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+/// Write iterator returned by [`SyncVecDeque::iter_mut`].
+pub struct VecDequeIterMut<'a, V> {
+    _w: WriteLock<'a>,
+    inner: DequeIterMut<'a, V>,
+}
+
+impl<'a, V> Iterator for VecDequeIterMut<'a, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, V> DoubleEndedIterator for VecDequeIterMut<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+/// A double-ended queue that can be safely shared between threads.
+///
+/// It wraps a `std::collections::VecDeque` with the same read/write
+/// separation as [`SyncVec`](super::SyncVec): `get`/`front`/`back`/`iter`/
+/// `len` only register a reader slot, and writers take the writer lock and
+/// wait for readers to drain. Pushing and popping at either end is O(1), so
+/// it is the container to use for FIFO queues instead of
+/// `SyncVec::remove(0)`.
+///
+/// # Deadlock note
+/// A read guard makes writers wait until it is dropped: drop it before
+/// calling a write method from the same thread. Debug builds detect this and
+/// panic; see [`DeadlockPolicy`](crate::sync::DeadlockPolicy).
+///
+/// # Examples
+///
+/// ```
+/// use dark_std::sync::SyncVecDeque;
+///
+/// let jobs = SyncVecDeque::new();
+/// jobs.push_back(1);
+/// jobs.push_back(2);
+/// jobs.push_front(0);
+/// assert_eq!(*jobs.front().unwrap(), 0);
+/// assert_eq!(jobs.pop_front(), Some(0));
+/// assert_eq!(jobs.pop_back(), Some(2));
+/// ```
+pub struct SyncVecDeque<V> {
+    dirty: SyncCell<VecDeque<V>>,
+}
+
+impl<V> SyncVecDeque<V> {
+    pub fn new_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    pub fn new() -> Self {
+        Self::with_deque(VecDeque::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_deque(VecDeque::with_capacity(capacity))
+    }
+
+    pub fn with_deque(deque: VecDeque<V>) -> Self {
+        Self {
+            dirty: SyncCell::named(deque, "SyncVecDeque"),
+        }
+    }
+
+    pub fn push_back(&self, v: V) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.push_back(v);
+    }
+
+    pub fn push_front(&self, v: V) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.push_front(v);
+    }
+
+    pub fn pop_back(&self) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop_back()
+    }
+
+    pub fn pop_front(&self) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.pop_front()
+    }
+
+    pub fn push_back_mut(&mut self, v: V) {
+        self.dirty.get_mut().push_back(v);
+    }
+
+    pub fn push_front_mut(&mut self, v: V) {
+        self.dirty.get_mut().push_front(v);
+    }
+
+    pub fn pop_back_mut(&mut self) -> Option<V> {
+        self.dirty.get_mut().pop_back()
+    }
+
+    pub fn pop_front_mut(&mut self) -> Option<V> {
+        self.dirty.get_mut().pop_front()
+    }
+
+    /// Pushes every element of `iter` to the back under one writer lock.
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = V>,
+    {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.extend(iter);
+    }
+
+    /// Inserts `v` at `index`, shifting the elements after it.
+    ///
+    /// Panics if `index` is greater than the length.
+    pub fn insert(&self, index: usize, v: V) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.insert(index, v);
+    }
+
+    pub fn remove(&self, index: usize) -> Option<V> {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.is_empty()
+    }
+
+    pub fn clear(&self) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.clear();
+    }
+
+    pub fn shrink_to_fit(&self) {
+        let _w = self.dirty.begin_write();
+        unsafe { &mut *self.dirty.data_ptr() }.shrink_to_fit();
+    }
+
+    /// Returns a read-guarded reference to the element at `index`, counting
+    /// from the front.
+    ///
+    /// The read is lock-free: it only registers a reader slot. Writers wait
+    /// for the returned guard to be dropped before mutating the queue.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<VecDequeGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.get(index).map(|v| ReadGuard::new(r, v))
+    }
+
+    pub fn front(&self) -> Option<VecDequeGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.front().map(|v| ReadGuard::new(r, v))
+    }
+
+    pub fn back(&self) -> Option<VecDequeGet<'_, V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        m.back().map(|v| ReadGuard::new(r, v))
+    }
+
+    /// Returns a write-guarded mutable reference to the element at `index`.
+    /// The guard holds the writer lock until it is dropped.
+    pub fn get_mut(&self, index: usize) -> Option<VecDequeRefMut<'_, V>> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        m.get_mut(index).map(|v| WriteGuard::new(w, v))
+    }
+
+    pub fn front_mut(&self) -> Option<VecDequeRefMut<'_, V>> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        m.front_mut().map(|v| WriteGuard::new(w, v))
+    }
+
+    pub fn back_mut(&self) -> Option<VecDequeRefMut<'_, V>> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        m.back_mut().map(|v| WriteGuard::new(w, v))
+    }
+
+    /// Runs `f` with exclusive access to the whole queue: one writer lock and
+    /// one reader drain for the whole batch.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut VecDeque<V>) -> R,
+    {
+        let _w = self.dirty.begin_write();
+        f(unsafe { &mut *self.dirty.data_ptr() })
+    }
+
+    /// Non-blocking version of [`SyncVecDeque::push_back`]: returns a
+    /// `WouldBlock` error instead of waiting when another writer holds the
+    /// lock or readers are active.
+    pub fn try_push_back(&self, v: V) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.push_back(v);
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncVecDeque::push_front`].
+    pub fn try_push_front(&self, v: V) -> Result<(), Error> {
+        let _w = self.dirty.try_begin_write()?;
+        unsafe { &mut *self.dirty.data_ptr() }.push_front(v);
+        Ok(())
+    }
+
+    /// Non-blocking version of [`SyncVecDeque::pop_back`].
+    pub fn try_pop_back(&self) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.pop_back())
+    }
+
+    /// Non-blocking version of [`SyncVecDeque::pop_front`].
+    pub fn try_pop_front(&self) -> Result<Option<V>, Error> {
+        let _w = self.dirty.try_begin_write()?;
+        Ok(unsafe { &mut *self.dirty.data_ptr() }.pop_front())
+    }
+
+    /// Contention counters of this queue, see [`Stats`].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.dirty.stats()
+    }
+
+    #[inline]
+    pub fn contains(&self, x: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        let _r = self.dirty.begin_read();
+        unsafe { &*self.dirty.data_ptr() }.contains(x)
+    }
+
+    pub fn iter(&self) -> VecDequeIter<'_, V> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        VecDequeIter {
+            _r: r,
+            inner: m.iter(),
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&self) -> VecDequeIterMut<'_, V> {
+        let w = self.dirty.begin_write();
+        let m = unsafe { &mut *self.dirty.data_ptr() };
+        VecDequeIterMut {
+            _w: w,
+            inner: m.iter_mut(),
+        }
+    }
+
+    pub fn dirty_ref(&self) -> ReadMapGuard<'_, VecDeque<V>> {
+        let r = self.dirty.begin_read();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        ReadMapGuard::new(r, m)
+    }
+
+    /// `Send` version of [`SyncVecDeque::dirty_ref`].
+    pub fn dirty_ref_send(&self) -> SendReadMapGuard<'_, VecDeque<V>> {
+        let r = self.dirty.begin_read_send();
+        let m = unsafe { &*self.dirty.data_ptr() };
+        SendReadMapGuard::new(r, m)
+    }
+
+    pub fn into_inner(self) -> VecDeque<V> {
+        self.dirty.into_inner()
+    }
+}
+
+impl<V> IntoIterator for SyncVecDeque<V> {
+    type Item = V;
+    type IntoIter = IntoIter<V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_inner().into_iter()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a SyncVecDeque<V> {
+    type Item = &'a V;
+    type IntoIter = VecDequeIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> From<VecDeque<V>> for SyncVecDeque<V> {
+    fn from(deque: VecDeque<V>) -> Self {
+        Self::with_deque(deque)
+    }
+}
+
+impl<V> From<Vec<V>> for SyncVecDeque<V> {
+    fn from(vec: Vec<V>) -> Self {
+        Self::with_deque(vec.into())
+    }
+}
+
+impl<V> Serialize for SyncVecDeque<V>
+where
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.dirty_ref().serialize(serializer)
+    }
+}
+
+impl<'de, V> serde::Deserialize<'de> for SyncVecDeque<V>
+where
+    V: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m = VecDeque::deserialize(deserializer)?;
+        Ok(Self::with_deque(m))
+    }
+}
+
+impl<V> Debug for SyncVecDeque<V>
+where
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.dirty_ref(), f)
+    }
+}
+
+impl<V> Display for SyncVecDeque<V>
+where
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.dirty_ref(), f)
+    }
+}
+
+impl<V: PartialEq> PartialEq for SyncVecDeque<V> {
+    fn eq(&self, other: &Self) -> bool {
+        (*self.dirty_ref()).eq(&*other.dirty_ref())
+    }
+}
+
+impl<V: Clone> Clone for SyncVecDeque<V> {
+    fn clone(&self) -> Self {
+        Self::with_deque(self.dirty_ref().clone())
+    }
+}
+
+impl<V> Default for SyncVecDeque<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
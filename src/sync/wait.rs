@@ -0,0 +1,168 @@
+use parking_lot::Mutex;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::Instant;
+
+/// How many times a blocking waiter re-checks its condition (spinning, then
+/// yielding) before it parks the thread.
+const SPIN_LIMIT: u32 = 10;
+
+/// A list of tasks and threads waiting for a container's lock state to change.
+///
+/// Whoever changes the state (a writer releasing the lock, a reader leaving
+/// while a writer waits) calls [`WaitList::notify_all`]; every waiter then
+/// re-checks its own condition. `len` mirrors the number of registered wakers
+/// so the uncontended paths only pay for one atomic load.
+pub(crate) struct WaitList {
+    len: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl WaitList {
+    pub(crate) const fn new() -> Self {
+        WaitList {
+            len: AtomicUsize::new(0),
+            wakers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Wakes every registered waiter. SeqCst pairs with the waiter's
+    /// register-then-recheck sequence, so a state change can never slip
+    /// between a waiter's last check and its registration.
+    #[inline]
+    pub(crate) fn notify_all(&self) {
+        if self.len.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let wakers = {
+            let mut wakers = self.wakers.lock();
+            self.len.store(0, Ordering::SeqCst);
+            std::mem::take(&mut *wakers)
+        };
+        for w in wakers {
+            w.wake();
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        self.len.store(wakers.len(), Ordering::SeqCst);
+    }
+
+    /// Blocks the calling thread until `cond` returns `true`.
+    ///
+    /// Short waits are absorbed by a bounded spin; after that the thread parks
+    /// and costs no CPU until a notification arrives, then re-checks `cond`.
+    pub(crate) fn block_until<F>(&self, cond: F)
+    where
+        F: Fn() -> bool,
+    {
+        self.block(cond, None);
+    }
+
+    /// Like [`WaitList::block_until`], but gives up at `deadline`. Returns
+    /// whether `cond` became `true`.
+    pub(crate) fn block_until_deadline<F>(&self, cond: F, deadline: Instant) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        self.block(cond, Some(deadline))
+    }
+
+    fn block<F>(&self, cond: F, deadline: Option<Instant>) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        for i in 0..SPIN_LIMIT {
+            if cond() {
+                return true;
+            }
+            if i < 4 {
+                for _ in 0..(1 << i) {
+                    std::hint::spin_loop();
+                }
+            } else {
+                std::thread::yield_now();
+            }
+        }
+        loop {
+            let parker = Arc::new(ThreadWaker {
+                thread: std::thread::current(),
+                notified: AtomicBool::new(false),
+            });
+            self.register(&Waker::from(parker.clone()));
+            if cond() {
+                return true;
+            }
+            while !parker.notified.load(Ordering::Acquire) {
+                match deadline {
+                    None => std::thread::park(),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            return cond();
+                        }
+                        std::thread::park_timeout(deadline - now);
+                    }
+                }
+            }
+            if cond() {
+                return true;
+            }
+        }
+    }
+
+    /// Returns a future that resolves once `cond` returns `true`. `cond` is
+    /// re-evaluated after every notification.
+    pub(crate) fn wait_until<F>(&self, cond: F) -> WaitUntil<'_, F>
+    where
+        F: Fn() -> bool,
+    {
+        WaitUntil { list: self, cond }
+    }
+}
+
+pub(crate) struct WaitUntil<'a, F> {
+    list: &'a WaitList,
+    cond: F,
+}
+
+impl<'a, F: Fn() -> bool> Future for WaitUntil<'a, F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if (self.cond)() {
+            return Poll::Ready(());
+        }
+        self.list.register(cx.waker());
+        if (self.cond)() {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+/// Wakes a parked thread; lets blocking threads share the waker list with
+/// async tasks.
+struct ThreadWaker {
+    thread: Thread,
+    notified: AtomicBool,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
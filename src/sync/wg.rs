@@ -56,6 +56,7 @@ impl Clone for WaitGroup {
 }
 
 impl WaitGroup {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let (s, r) = flume::unbounded();
         Self {
@@ -76,18 +77,11 @@ impl WaitGroup {
         }
         let mut total;
         let mut current = 0;
-        loop {
-            match self.recv.recv_async().await {
-                Ok(v) => {
-                    current += v;
-                    total = self.total.fetch_or(0, Ordering::SeqCst);
-                    if current >= total {
-                        break;
-                    }
-                }
-                Err(_) => {
-                    break;
-                }
+        while let Ok(v) = self.recv.recv_async().await {
+            current += v;
+            total = self.total.fetch_or(0, Ordering::SeqCst);
+            if current >= total {
+                break;
             }
         }
     }
@@ -98,18 +92,11 @@ impl WaitGroup {
         }
         let mut total;
         let mut current = 0;
-        loop {
-            match self.recv.recv() {
-                Ok(v) => {
-                    current += v;
-                    total = self.total.fetch_or(0, Ordering::SeqCst);
-                    if current >= total {
-                        break;
-                    }
-                }
-                Err(_) => {
-                    break;
-                }
+        while let Ok(v) = self.recv.recv() {
+            current += v;
+            total = self.total.fetch_or(0, Ordering::SeqCst);
+            if current >= total {
+                break;
             }
         }
     }
@@ -1,6 +1,7 @@
 use dark_std::sync::SyncBtreeMap;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[test]
 pub fn test_empty() {
@@ -12,7 +13,7 @@ pub fn test_empty() {
 pub fn test_insert() {
     let m = SyncBtreeMap::<i32, i32>::new();
     let insert = m.insert(1, 2);
-    assert_eq!(insert.is_none(), true);
+    assert!(insert.is_none());
 }
 
 #[test]
@@ -134,3 +135,189 @@ pub fn test_iter_mut_deref() {
     let it = m.iter_mut();
     assert_eq!(it.len(), 2); // via Deref to the inner iterator
 }
+
+#[tokio::test]
+async fn test_insert_async() {
+    let m = SyncBtreeMap::<i32, i32>::new_arc();
+    let m2 = m.clone();
+    tokio::spawn(async move {
+        m2.insert_async(1, 2).await;
+    })
+    .await
+    .unwrap();
+    assert_eq!(m.read_async(|m| m.get(&1).copied()).await, Some(2));
+    *m.get_mut_async(&1).await.unwrap() = 3;
+    m.write_async(|m| m.insert(2, 4)).await;
+    assert_eq!(m.remove_async(&1).await, Some(3));
+    assert_eq!(m.len(), 1);
+}
+
+#[test]
+pub fn test_try_insert() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    assert_eq!(m.try_insert(1, 1).unwrap(), None);
+    let g = m.get(&1).unwrap();
+    assert!(m.try_insert(1, 2).is_err());
+    drop(g);
+    assert_eq!(m.try_insert(1, 2).unwrap(), Some(1));
+    assert_eq!(*m.get(&1).unwrap(), 2);
+}
+
+#[test]
+pub fn test_insert_timeout() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get(&1).unwrap();
+    assert!(m.insert_timeout(1, 2, Duration::from_millis(20)).is_err());
+    drop(g);
+    assert_eq!(
+        m.insert_timeout(1, 2, Duration::from_millis(20)).unwrap(),
+        Some(1)
+    );
+}
+
+#[test]
+pub fn test_get_send() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get_send(&1).unwrap();
+    std::thread::scope(|s| {
+        s.spawn(move || assert_eq!(*g, 1));
+    });
+    assert_eq!(m.insert(1, 2), Some(1));
+    assert_eq!(m.dirty_ref_send().len(), 1);
+}
+
+#[test]
+pub fn test_get_owned() {
+    let m = SyncBtreeMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    m.insert(2, 2);
+    let g = m.get_owned(&1).unwrap();
+    let it = m.iter_owned();
+    drop(m);
+    assert_eq!(*g, 1);
+    assert_eq!(it.collect::<Vec<_>>(), vec![(1, 1), (2, 2)]);
+}
+
+#[test]
+pub fn test_transact_rollback() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let r: Result<(), _> = m.transact(|tx| {
+        tx.insert(2, 2);
+        tx.remove(&1);
+        Err(dark_std::err!("abort"))
+    });
+    assert!(r.is_err());
+    assert_eq!(*m.dirty_ref(), [(1, 1)].into_iter().collect());
+    m.write(|m| m.insert(2, 2));
+    assert_eq!(m.len(), 2);
+}
+
+#[test]
+pub fn test_upgradable_read() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    let g = m.upgradable_read();
+    let r = m.dirty_ref();
+    assert!(g.is_empty() && r.is_empty());
+    drop(r);
+    g.upgrade().insert(1, 1);
+    assert_eq!(*m.get(&1).unwrap(), 1);
+}
+
+#[test]
+pub fn test_snapshot() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let snap = m.snapshot();
+    // the snapshot holds no reader slot: writers do not wait for it
+    m.try_insert(2, 2).unwrap();
+    *m.get_mut(&1).unwrap() = 10;
+    assert_eq!(*snap, [(1, 1)].into_iter().collect());
+    let g = m.upgradable_read();
+    let snap2 = std::thread::scope(|s| s.spawn(|| m.snapshot()).join().unwrap());
+    g.upgrade().clear();
+    assert_eq!(snap2.len(), 2);
+    assert!(m.is_empty());
+    drop(snap);
+    assert_eq!(m.into_inner().len(), 0);
+}
+
+#[test]
+pub fn test_entry() {
+    use dark_std::sync::map_btree::BtreeMapEntry;
+    let m = SyncBtreeMap::<i32, i32>::new();
+    *m.entry(1).or_insert(1) += 1;
+    m.entry(1).and_modify(|v| *v *= 10).or_insert(0);
+    m.entry(2).or_insert_with(|| 3);
+    assert_eq!(*m.dirty_ref(), [(1, 20), (2, 3)].into_iter().collect());
+    if let BtreeMapEntry::Occupied(mut e) = m.entry(2) {
+        assert_eq!(e.insert(4), 3);
+        assert_eq!(e.remove(), 4);
+    }
+    assert_eq!(m.len(), 1);
+}
+
+#[test]
+pub fn test_read_modify_write() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    assert_eq!(*m.get_or_insert_with(1, || 1), 1);
+    m.compute(1, |v| v.map(|v| v * 10));
+    assert_eq!(m.update(&1, |v| *v), Some(10));
+    assert_eq!(m.compare_and_swap(&1, &10, 11), Ok(10));
+    assert_eq!(*m.get(&1).unwrap(), 11);
+}
+
+#[test]
+pub fn test_bulk_ops() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.extend((0..10).map(|i| (i, i)));
+    assert_eq!(m.insert_many([(1, 10), (20, 20)]), vec![Some(1), None]);
+    m.retain(|k, v| {
+        *v += 1;
+        k % 2 == 0
+    });
+    assert_eq!(m.remove_many(&[0, 1, 2]), vec![1, 3]);
+    let other = SyncBtreeMap::new();
+    other.insert(100, 100);
+    m.append(&other);
+    assert!(other.is_empty());
+    let drained: Vec<_> = m.drain().collect();
+    assert_eq!(drained, vec![(4, 5), (6, 7), (8, 9), (20, 21), (100, 100)]);
+    assert!(m.is_empty());
+}
+
+#[test]
+pub fn test_range_and_navigation() {
+    let m = SyncBtreeMap::<i32, i32>::new();
+    m.extend([(10, 1), (20, 2), (30, 3), (40, 4)]);
+    let keys: Vec<_> = m.range(15..=30).map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec![20, 30]);
+    assert_eq!(m.range(..).next_back().map(|(k, _)| *k), Some(40));
+    for (_, v) in m.range_mut(20..) {
+        *v *= 10;
+    }
+    assert_eq!(*m.get(&10).unwrap(), 1);
+    assert_eq!(*m.get(&40).unwrap(), 40);
+
+    let first = m.first_key_value().unwrap();
+    assert_eq!((*first.key(), *first.value()), (10, 1));
+    drop(first);
+    assert_eq!(*m.last_key_value().unwrap().key(), 40);
+    assert_eq!(*m.floor(&25).unwrap().key(), 20);
+    assert_eq!(*m.floor(&20).unwrap(), 20);
+    assert!(m.floor(&5).is_none());
+    assert_eq!(*m.ceiling(&25).unwrap().key(), 30);
+    assert!(m.ceiling(&41).is_none());
+
+    let tail = m.split_off(&30);
+    assert_eq!(m.len(), 2);
+    assert_eq!(
+        tail.into_inner().into_iter().collect::<Vec<_>>(),
+        vec![(30, 30), (40, 40)]
+    );
+    assert_eq!(m.pop_first(), Some((10, 1)));
+    assert_eq!(m.pop_last(), Some((20, 20)));
+    assert_eq!(m.pop_last(), None);
+}
@@ -0,0 +1,125 @@
+use dark_std::sync::SyncCell;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+pub fn test_debug() {
+    let c = SyncCell::new(VecDeque::from(vec![1, 2]));
+    assert_eq!(format!("{:?}", c), "[1, 2]");
+}
+
+#[test]
+pub fn test_read() {
+    let c = SyncCell::new(HashSet::new());
+    c.write().insert(1);
+    let g = c.read();
+    assert!(g.contains(&1));
+    assert_eq!(g.len(), 1);
+}
+
+#[test]
+pub fn test_write() {
+    let c = SyncCell::new(VecDeque::new());
+    let mut w = c.write();
+    w.push_back(1);
+    w.push_front(0);
+    drop(w); // write guard holds the lock; drop it before reading
+    assert_eq!(*c.read(), VecDeque::from(vec![0, 1]));
+}
+
+#[test]
+pub fn test_get_mut() {
+    let mut c = SyncCell::new(vec![1]);
+    c.get_mut().push(2);
+    assert_eq!(c.into_inner(), vec![1, 2]);
+}
+
+#[test]
+pub fn test_threads() {
+    let c = SyncCell::new_arc(VecDeque::new());
+    let mut handles = vec![];
+    for i in 0..8 {
+        let c = c.clone();
+        handles.push(std::thread::spawn(move || {
+            for j in 0..100 {
+                c.write().push_back(i * 100 + j);
+                assert!(!c.read().is_empty());
+            }
+        }));
+    }
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(c.read().len(), 800);
+}
+
+#[test]
+pub fn test_clone() {
+    let c = Arc::new(SyncCell::new(vec![1, 2, 3]));
+    let c2 = (*c).clone();
+    c.write().clear();
+    assert_eq!(*c2.read(), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_write_async() {
+    let c = SyncCell::new_arc(VecDeque::new());
+    let c2 = c.clone();
+    tokio::spawn(async move {
+        c2.write_async().await.push_back(1);
+    })
+    .await
+    .unwrap();
+    assert_eq!(c.read_async().await.front(), Some(&1));
+}
+
+#[test]
+pub fn test_try_write() {
+    let c = SyncCell::new(1);
+    let r = c.read();
+    assert!(c.try_write().is_err());
+    drop(r);
+    *c.try_write().unwrap() = 2;
+    assert_eq!(*c.read(), 2);
+}
+
+#[test]
+pub fn test_write_timeout() {
+    let c = SyncCell::new(1);
+    let r = c.read();
+    assert!(c.write_timeout(Duration::from_millis(20)).is_err());
+    drop(r);
+    *c.write_timeout(Duration::from_millis(20)).unwrap() = 2;
+    assert_eq!(*c.read(), 2);
+}
+
+#[test]
+pub fn test_read_send() {
+    let c = SyncCell::new(1);
+    let r = c.read_send();
+    std::thread::scope(|s| {
+        s.spawn(move || assert_eq!(*r, 1));
+    });
+    *c.write() = 2;
+    assert_eq!(*c.read_send(), 2);
+}
+
+#[test]
+pub fn test_read_owned() {
+    let c = SyncCell::new_arc(1);
+    let r = c.read_owned();
+    let h = std::thread::spawn(move || *r);
+    assert_eq!(h.join().unwrap(), 1);
+    *c.write() = 2;
+}
+
+#[test]
+pub fn test_upgradable_read() {
+    let c = SyncCell::new(1);
+    let g = c.upgradable_read();
+    assert_eq!(*c.read(), 1);
+    assert!(c.try_write().is_err());
+    *g.upgrade() = 2;
+    assert_eq!(*c.read(), 2);
+}
@@ -0,0 +1,26 @@
+use std::sync::Barrier;
+
+/// Runs `f` while another thread holds the read guard returned by `read`, so
+/// `f` sees an active reader without tripping the same-thread deadlock check.
+/// The guard is dropped once `f` returns (or panics).
+pub fn with_reader_elsewhere<G, R>(read: impl FnOnce() -> G + Send, f: impl FnOnce() -> R) -> R {
+    struct Release<'a>(&'a Barrier);
+
+    impl Drop for Release<'_> {
+        fn drop(&mut self) {
+            self.0.wait();
+        }
+    }
+
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let _g = read();
+            barrier.wait();
+            barrier.wait();
+        });
+        barrier.wait();
+        let _release = Release(&barrier);
+        f()
+    })
+}
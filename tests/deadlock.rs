@@ -0,0 +1,47 @@
+use dark_std::sync::{deadlock_policy, set_deadlock_policy, DeadlockPolicy, SyncHashMap, SyncVec};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+// The policy is process-wide, so every case runs in this one test.
+#[test]
+pub fn test_same_thread_read_then_write() {
+    if cfg!(debug_assertions) {
+        assert_eq!(deadlock_policy(), DeadlockPolicy::Panic);
+    }
+
+    set_deadlock_policy(DeadlockPolicy::Panic);
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get(&1).unwrap();
+    let r = catch_unwind(AssertUnwindSafe(|| m.insert(1, 2)));
+    let msg = r.unwrap_err().downcast::<String>().unwrap();
+    assert!(msg.contains("SyncHashMap"), "{}", msg);
+    assert_eq!(*g, 1);
+    drop(g);
+    assert_eq!(m.insert(1, 2), Some(1));
+
+    set_deadlock_policy(DeadlockPolicy::Error);
+    let v = SyncVec::<i32>::new();
+    v.push(1);
+    let g = v.get(0).unwrap();
+    let e = v.try_push(2).unwrap_err().to_string();
+    assert!(e.starts_with("Deadlock") && e.contains("SyncVec"), "{}", e);
+    let e = v.push_timeout(2, Duration::from_millis(20)).unwrap_err();
+    assert!(e.to_string().starts_with("Deadlock"));
+    drop(g);
+    v.try_push(2).unwrap();
+
+    set_deadlock_policy(DeadlockPolicy::Block);
+    let g = m.get(&1).unwrap();
+    let e = m
+        .insert_timeout(1, 3, Duration::from_millis(20))
+        .unwrap_err();
+    assert!(e.to_string().starts_with("Timeout"));
+    drop(g);
+
+    // a guard on a different container is not a deadlock
+    set_deadlock_policy(DeadlockPolicy::Panic);
+    let g = v.get(0).unwrap();
+    assert_eq!(m.insert(1, 3), Some(2));
+    drop(g);
+}
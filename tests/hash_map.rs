@@ -1,8 +1,12 @@
 use dark_std::sync::SyncHashMap;
 
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+mod common;
 
 #[test]
 pub fn test_debug() {
@@ -22,7 +26,7 @@ pub fn test_empty() {
 pub fn test_insert() {
     let m = SyncHashMap::<i32, i32>::new();
     let insert = m.insert(1, 2);
-    assert_eq!(insert.is_none(), true);
+    assert!(insert.is_none());
 }
 
 #[test]
@@ -128,8 +132,8 @@ pub fn test_remove() {
     let rm = m.remove(&1).unwrap();
     println!("rm:{:?}", rm);
     drop(rm);
-    assert_eq!(true, m.is_empty());
-    assert_eq!(true, m.dirty_ref().is_empty());
+    assert!(m.is_empty());
+    assert!(m.dirty_ref().is_empty());
     assert_eq!(None, m.get(&1));
 }
 
@@ -272,3 +276,479 @@ pub fn test_iter_mut_deref() {
     let it = m.iter_mut();
     assert_eq!(it.len(), 2); // via Deref to the inner iterator
 }
+
+#[tokio::test]
+async fn test_insert_async() {
+    let m = SyncHashMap::<i32, i32>::new_arc();
+    let m2 = m.clone();
+    // the future must be `Send` to run on a multi-threaded runtime
+    tokio::spawn(async move {
+        m2.insert_async(1, 2).await;
+    })
+    .await
+    .unwrap();
+    assert_eq!(m.read_async(|m| m.get(&1).copied()).await, Some(2));
+    *m.get_mut_async(&1).await.unwrap() = 3;
+    m.write_async(|m| m.insert(2, 4)).await;
+    assert_eq!(m.remove_async(&1).await, Some(3));
+    assert_eq!(m.len(), 1);
+}
+
+// An async writer waits for a guard held on another thread without blocking
+// its worker thread, and completes as soon as the guard is dropped.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_insert_async_waits_for_reader() {
+    let m = SyncHashMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    let (held, release) = (
+        std::sync::mpsc::channel::<()>(),
+        std::sync::mpsc::channel::<()>(),
+    );
+    let m2 = m.clone();
+    let reader = std::thread::spawn(move || {
+        let g = m2.get(&1).unwrap();
+        held.0.send(()).unwrap();
+        release.1.recv().unwrap();
+        drop(g);
+    });
+    held.1.recv().unwrap();
+    let writer = tokio::spawn({
+        let m = m.clone();
+        async move { m.insert_async(1, 2).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!writer.is_finished());
+    release.0.send(()).unwrap();
+    assert_eq!(writer.await.unwrap(), Some(1));
+    reader.join().unwrap();
+    assert_eq!(*m.get(&1).unwrap(), 2);
+}
+
+#[test]
+pub fn test_try_insert() {
+    let m = SyncHashMap::<i32, i32>::new();
+    assert_eq!(m.try_insert(1, 1).unwrap(), None);
+    let g = m.get(&1).unwrap();
+    let e = m.try_insert(1, 2).unwrap_err();
+    assert!(e.to_string().starts_with("WouldBlock"));
+    assert!(m.try_get_mut(&1).is_err());
+    drop(g);
+    assert_eq!(m.try_insert(1, 2).unwrap(), Some(1));
+    assert_eq!(m.try_remove(&1).unwrap(), Some(2));
+}
+
+#[test]
+pub fn test_try_insert_while_writer_held() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let mut g = m.get_mut(&1).unwrap();
+    assert!(m.try_clear().is_err());
+    *g = 2;
+    drop(g);
+    // a failed try_* must not leave the writing flag raised
+    assert_eq!(*m.get(&1).unwrap(), 2);
+    m.try_clear().unwrap();
+    assert!(m.is_empty());
+}
+
+// A leaked read guard bounds the write instead of hanging it, and readers keep
+// going after the writer gives up.
+#[test]
+pub fn test_insert_timeout() {
+    let m = SyncHashMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    let g = m.get(&1).unwrap();
+    let e = m
+        .insert_timeout(1, 2, Duration::from_millis(50))
+        .unwrap_err();
+    assert!(e.to_string().starts_with("Timeout"));
+    let m2 = m.clone();
+    let reader = std::thread::spawn(move || *m2.get(&1).unwrap());
+    assert_eq!(reader.join().unwrap(), 1);
+    drop(g);
+    assert_eq!(
+        m.insert_timeout(1, 2, Duration::from_millis(50)).unwrap(),
+        Some(1)
+    );
+}
+
+#[test]
+pub fn test_default_write_timeout() {
+    let m = SyncHashMap::<i32, i32>::new();
+    assert_eq!(m.default_write_timeout(), None);
+    m.set_default_write_timeout(Some(Duration::from_millis(20)));
+    assert_eq!(m.default_write_timeout(), Some(Duration::from_millis(20)));
+    m.insert(1, 1);
+    // `try_insert` waits for the default timeout instead of failing at once
+    let e =
+        common::with_reader_elsewhere(|| m.get(&1).unwrap(), || m.try_insert(1, 2)).unwrap_err();
+    assert!(e.to_string().starts_with("Timeout"));
+    // a blocking write outlives the default timeout instead of panicking
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let _g = m.get(&1).unwrap();
+            barrier.wait();
+            sleep(Duration::from_millis(50));
+        });
+        barrier.wait();
+        assert_eq!(m.insert(1, 2), Some(1));
+    });
+    assert_eq!(m.try_insert(1, 3).unwrap(), Some(2));
+}
+
+// A `get_send` guard can live across an `.await` in a spawned task, and the
+// writer waits for it wherever it is dropped.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_get_send_across_await() {
+    let m = SyncHashMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    let m2 = m.clone();
+    let v = tokio::spawn(async move {
+        let g = m2.get_send(&1).unwrap();
+        tokio::task::yield_now().await;
+        let sum: i32 = m2.dirty_ref_send().values().sum();
+        *g + sum
+    })
+    .await
+    .unwrap();
+    assert_eq!(v, 2);
+    assert_eq!(m.insert(1, 2), Some(1));
+}
+
+#[test]
+pub fn test_get_send_dropped_on_other_thread() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get_send(&1).unwrap();
+    assert!(m.try_insert(1, 2).is_err());
+    std::thread::scope(|s| {
+        s.spawn(move || assert_eq!(*g, 1));
+    });
+    assert_eq!(m.insert(1, 2), Some(1));
+}
+
+// Owned guards borrow nothing: they can be returned from a function that owns
+// the `Arc` and moved into a spawned task.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_get_owned() {
+    fn first(m: Arc<SyncHashMap<i32, i32>>) -> dark_std::sync::map_hash::HashMapGetOwned<i32, i32> {
+        m.get_owned(&1).unwrap()
+    }
+    let m = SyncHashMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    m.insert(2, 2);
+    let g = first(m.clone());
+    let it = m.iter_owned();
+    let all = m.read_owned();
+    let (v, mut items) = tokio::spawn(async move {
+        tokio::task::yield_now().await;
+        (*g, it.collect::<Vec<_>>())
+    })
+    .await
+    .unwrap();
+    items.sort();
+    assert_eq!(v, 1);
+    assert_eq!(items, vec![(1, 1), (2, 2)]);
+    assert_eq!(all.len(), 2);
+    assert!(m.try_insert(3, 3).is_err());
+    drop(all);
+    assert_eq!(m.insert(3, 3), None);
+}
+
+#[test]
+pub fn test_get_owned_keeps_map_alive() {
+    let m = SyncHashMap::<i32, String>::new_arc();
+    m.insert(1, "a".to_string());
+    let g = m.get_owned(&1).unwrap();
+    drop(m);
+    assert_eq!(*g, "a");
+}
+
+#[test]
+pub fn test_write_batch() {
+    let m = SyncHashMap::<i32, i32>::new();
+    let n = m.write(|m| {
+        for i in 0..10 {
+            m.insert(i, i);
+        }
+        m.remove(&0);
+        m.len()
+    });
+    assert_eq!(n, 9);
+    assert_eq!(m.len(), 9);
+}
+
+#[test]
+pub fn test_transact_rollback() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    m.insert(2, 2);
+    let r: Result<(), _> = m.transact(|tx| {
+        tx.insert(1, 10);
+        tx.insert(3, 3);
+        tx.remove(&2);
+        *tx.get_mut(&1).unwrap() += 1;
+        assert_eq!(tx.len(), 2);
+        Err(dark_std::err!("abort"))
+    });
+    assert_eq!(r.unwrap_err().to_string(), "abort");
+    let mut all: Vec<_> = m.iter().map(|(k, v)| (*k, *v)).collect();
+    all.sort();
+    assert_eq!(all, vec![(1, 1), (2, 2)]);
+
+    let r = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        m.transact(|tx| -> Result<(), _> {
+            tx.clear();
+            panic!("boom");
+        })
+    }));
+    assert!(r.is_err());
+    assert_eq!(m.len(), 2);
+
+    let old = m.transact(|tx| Ok(tx.insert(1, 10))).unwrap();
+    assert_eq!(old, Some(1));
+    assert_eq!(*m.get(&1).unwrap(), 10);
+}
+
+// A reader never observes half of a batch: both keys always move together.
+#[test]
+pub fn test_write_batch_is_atomic() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 0);
+    m.insert(2, 0);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..1000 {
+                let g = m.dirty_ref();
+                assert_eq!(g[&1], g[&2]);
+            }
+        });
+        for i in 1..=200 {
+            let _ = m.transact(|tx| {
+                tx.insert(1, i);
+                tx.insert(2, i);
+                if i % 2 == 0 {
+                    return Err(dark_std::err!("rollback"));
+                }
+                Ok(())
+            });
+        }
+    });
+    assert_eq!(*m.get(&1).unwrap(), 199);
+}
+
+// The upgradable guard coexists with readers on other threads, keeps writers
+// out until it is dropped or upgraded, and upgrades once the readers drain.
+#[test]
+pub fn test_upgradable_read() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.upgradable_read();
+    let barrier = Barrier::new(3);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let r = m.get(&1).unwrap();
+            barrier.wait();
+            sleep(Duration::from_millis(50));
+            assert_eq!(*r, 1);
+        });
+        let writer = s.spawn(|| {
+            barrier.wait();
+            m.insert(1, 3)
+        });
+        barrier.wait();
+        assert!(m.try_insert(1, 0).is_err());
+        assert_eq!(g[&1], 1);
+        let mut w = g.upgrade();
+        w.insert(1, 2);
+        drop(w);
+        assert_eq!(writer.join().unwrap(), Some(2));
+    });
+    assert_eq!(*m.get(&1).unwrap(), 3);
+}
+
+#[test]
+pub fn test_snapshot() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let snap = m.snapshot();
+    m.try_insert(2, 2).unwrap();
+    assert_eq!(snap.len(), 1);
+    let snap2 = m.snapshot();
+    m.write(|m| m.clear());
+    assert_eq!(snap2.len(), 2);
+    // into_inner copies the data out of a live snapshot
+    m.insert(3, 3);
+    let snap3 = m.snapshot();
+    assert_eq!(m.into_inner(), *snap3);
+}
+
+static COPYING: AtomicBool = AtomicBool::new(false);
+static READ_DURING_COPY: AtomicBool = AtomicBool::new(false);
+
+/// A value whose copy waits until a reader got through.
+#[derive(Debug)]
+struct WaitsForReader(i32);
+
+impl Clone for WaitsForReader {
+    fn clone(&self) -> Self {
+        COPYING.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        while !READ_DURING_COPY.load(Ordering::SeqCst) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "readers were held off"
+            );
+            std::thread::yield_now();
+        }
+        WaitsForReader(self.0)
+    }
+}
+
+// The first write after a snapshot copies the map before it holds off
+// readers, so they keep reading during the copy.
+#[test]
+pub fn test_snapshot_copy_does_not_block_readers() {
+    let m = SyncHashMap::new();
+    m.insert(1, WaitsForReader(1));
+    let snap = m.snapshot();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            while !COPYING.load(Ordering::SeqCst) {
+                std::thread::yield_now();
+            }
+            assert_eq!(m.get(&1).unwrap().0, 1);
+            READ_DURING_COPY.store(true, Ordering::SeqCst);
+        });
+        m.insert(2, WaitsForReader(2));
+    });
+    assert_eq!(snap.len(), 1);
+    assert_eq!(m.len(), 2);
+}
+
+#[test]
+pub fn test_entry() {
+    use dark_std::sync::map_hash::HashMapEntry;
+    let m = SyncHashMap::<&str, i32>::new();
+    *m.entry("a").or_insert(1) += 1;
+    m.entry("a").and_modify(|v| *v *= 10).or_insert(0);
+    *m.entry("b").or_default() += 5;
+    m.entry("c").or_insert_with_key(|k| k.len() as i32);
+    assert_eq!(*m.get("a").unwrap(), 20);
+    assert_eq!(*m.get("b").unwrap(), 5);
+    assert_eq!(*m.get("c").unwrap(), 1);
+    match m.entry("b") {
+        HashMapEntry::Occupied(e) => assert_eq!(e.remove_entry(), ("b", 5)),
+        HashMapEntry::Vacant(_) => unreachable!(),
+    }
+    match m.entry("b") {
+        HashMapEntry::Occupied(_) => unreachable!(),
+        HashMapEntry::Vacant(e) => assert_eq!(e.into_key(), "b"),
+    }
+    assert_eq!(m.len(), 2);
+}
+
+#[test]
+pub fn test_entry_is_atomic() {
+    let m = SyncHashMap::<i32, i32>::new();
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..1000 {
+                    *m.entry(1).or_insert(0) += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(*m.get(&1).unwrap(), 4000);
+}
+
+#[test]
+pub fn test_read_modify_write() {
+    let m = SyncHashMap::<i32, i32>::new();
+    let mut calls = 0;
+    assert_eq!(
+        *m.get_or_insert_with(1, || {
+            calls += 1;
+            1
+        }),
+        1
+    );
+    assert_eq!(*m.get_or_insert_with(1, || unreachable!()), 1);
+    assert_eq!(calls, 1);
+    assert_eq!(m.compute(1, |v| v.map(|v| v + 1)), Some(1));
+    assert_eq!(m.compute(2, |v| v.map(|v| v + 1)), None);
+    assert!(!m.contains_key(&2));
+    assert_eq!(m.update(&1, |v| std::mem::replace(v, 5)), Some(2));
+    assert_eq!(m.update(&2, |v| *v += 1), None);
+    assert_eq!(m.compare_and_swap(&1, &4, 6), Err(6));
+    assert_eq!(m.compare_and_swap(&1, &5, 6), Ok(5));
+    assert_eq!(m.compare_and_swap(&2, &5, 6), Err(6));
+    assert_eq!(m.compute(1, |_| None), Some(6));
+    assert!(m.is_empty());
+}
+
+#[test]
+pub fn test_get_or_insert_with_race() {
+    let m = SyncHashMap::<i32, i32>::new();
+    let calls = std::sync::atomic::AtomicUsize::new(0);
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let g = m.get_or_insert_with(1, || {
+                    calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    1
+                });
+                assert_eq!(*g, 1);
+            });
+        }
+    });
+    assert_eq!(calls.into_inner(), 1);
+}
+
+#[test]
+pub fn test_bulk_ops() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.extend((0..10).map(|i| (i, i)));
+    assert_eq!(m.len(), 10);
+    assert_eq!(m.insert_many([(1, 10), (20, 20)]), vec![Some(1), None]);
+    m.retain(|k, v| {
+        *v += 1;
+        k % 2 == 0
+    });
+    assert_eq!(m.len(), 6);
+    assert_eq!(*m.get(&2).unwrap(), 3);
+    assert_eq!(m.remove_many(&[0, 1, 2]), vec![1, 3]);
+    let other = SyncHashMap::new();
+    other.insert(100, 100);
+    m.append(&other);
+    assert!(other.is_empty());
+    m.append(&m);
+    let mut drained: Vec<_> = m.drain().collect();
+    drained.sort();
+    assert_eq!(drained, vec![(4, 5), (6, 7), (8, 9), (20, 21), (100, 100)]);
+    assert!(m.is_empty());
+}
+
+// Opposite `append`s lock the two maps in the same order, so they neither
+// deadlock nor lose entries.
+#[test]
+pub fn test_append_both_ways() {
+    let a = SyncHashMap::new();
+    let b = SyncHashMap::new();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..1000 {
+                a.insert(i, i);
+                a.append(&b);
+            }
+        });
+        for i in 1000..2000 {
+            b.insert(i, i);
+            b.append(&a);
+        }
+    });
+    a.append(&b);
+    assert_eq!(a.len(), 2000);
+}
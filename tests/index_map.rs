@@ -22,7 +22,7 @@ pub fn test_empty() {
 pub fn test_insert() {
     let m = SyncIndexMap::<i32, i32>::new();
     let insert = m.insert(1, 2);
-    assert_eq!(insert.is_none(), true);
+    assert!(insert.is_none());
 }
 
 #[test]
@@ -128,8 +128,8 @@ pub fn test_remove() {
     let rm = m.remove(&1).unwrap();
     println!("rm:{:?}", rm);
     drop(rm);
-    assert_eq!(true, m.is_empty());
-    assert_eq!(true, m.dirty_ref().is_empty());
+    assert!(m.is_empty());
+    assert!(m.dirty_ref().is_empty());
     assert_eq!(None, m.get(&1));
 }
 
@@ -259,3 +259,202 @@ pub fn test_iter_mut_deref() {
     let it = m.iter_mut();
     assert_eq!(it.len(), 2); // via Deref to the inner iterator
 }
+
+#[tokio::test]
+async fn test_insert_async() {
+    let m = SyncIndexMap::<i32, i32>::new_arc();
+    let m2 = m.clone();
+    tokio::spawn(async move {
+        m2.insert_async(1, 2).await;
+    })
+    .await
+    .unwrap();
+    assert_eq!(m.read_async(|m| m.get(&1).copied()).await, Some(2));
+    *m.get_mut_async(&1).await.unwrap() = 3;
+    m.write_async(|m| m.insert(2, 4)).await;
+    assert_eq!(m.remove_async(&1).await, Some(3));
+    assert_eq!(m.len(), 1);
+}
+
+#[test]
+pub fn test_try_insert() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    assert_eq!(m.try_insert(1, 1).unwrap(), None);
+    let g = m.get(&1).unwrap();
+    assert!(m.try_insert(1, 2).is_err());
+    drop(g);
+    assert_eq!(m.try_insert(1, 2).unwrap(), Some(1));
+    assert_eq!(*m.get(&1).unwrap(), 2);
+}
+
+#[test]
+pub fn test_insert_timeout() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get(&1).unwrap();
+    assert!(m.insert_timeout(1, 2, Duration::from_millis(20)).is_err());
+    drop(g);
+    assert_eq!(
+        m.insert_timeout(1, 2, Duration::from_millis(20)).unwrap(),
+        Some(1)
+    );
+}
+
+#[test]
+pub fn test_get_send() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    m.insert(1, 1);
+    let g = m.get_send(&1).unwrap();
+    std::thread::scope(|s| {
+        s.spawn(move || assert_eq!(*g, 1));
+    });
+    assert_eq!(m.insert(1, 2), Some(1));
+    assert_eq!(m.dirty_ref_send().len(), 1);
+}
+
+#[test]
+pub fn test_get_owned() {
+    let m = SyncIndexMap::<i32, i32>::new_arc();
+    m.insert(1, 1);
+    m.insert(2, 2);
+    let g = m.get_owned(&2).unwrap();
+    let it = m.iter_owned();
+    let all = m.read_owned();
+    drop(m);
+    assert_eq!(*g, 2);
+    assert_eq!(it.collect::<Vec<_>>(), vec![(1, 1), (2, 2)]);
+    assert_eq!(all.len(), 2);
+}
+
+// Rolling back restores the insertion order too, including `swap_remove`.
+#[test]
+pub fn test_transact_rollback() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    for i in 0..4 {
+        m.insert(i, i);
+    }
+    let r: Result<(), _> = m.transact(|tx| {
+        tx.remove(&1);
+        tx.insert(9, 9);
+        tx.insert(2, 20);
+        *tx.get_mut(&3).unwrap() = 30;
+        tx.remove(&0);
+        Err(dark_std::err!("abort"))
+    });
+    assert!(r.is_err());
+    let all: Vec<_> = m.iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(all, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+}
+
+#[test]
+pub fn test_upgradable_read() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    let g = m.upgradable_read();
+    assert!(m.try_insert(1, 1).is_err());
+    if !g.contains_key(&1) {
+        g.upgrade().insert(1, 2);
+    }
+    assert_eq!(*m.get(&1).unwrap(), 2);
+}
+
+#[test]
+pub fn test_entry() {
+    use dark_std::sync::map_index::IndexMapEntry;
+    let m = SyncIndexMap::<i32, i32>::new();
+    for i in 0..3 {
+        m.entry(i).or_insert(i);
+    }
+    match m.entry(3) {
+        IndexMapEntry::Vacant(e) => {
+            assert_eq!(e.index(), 3);
+            e.insert(3);
+        }
+        IndexMapEntry::Occupied(_) => unreachable!(),
+    }
+    match m.entry(0) {
+        IndexMapEntry::Occupied(e) => assert_eq!(e.shift_remove_entry(), (0, 0)),
+        IndexMapEntry::Vacant(_) => unreachable!(),
+    }
+    let keys: Vec<_> = m.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec![1, 2, 3]);
+    if let IndexMapEntry::Occupied(e) = m.entry(1) {
+        assert_eq!(e.index(), 0);
+        assert_eq!(e.remove(), 1);
+    }
+    let keys: Vec<_> = m.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec![3, 2]);
+}
+
+#[test]
+pub fn test_read_modify_write() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    assert_eq!(*m.get_or_insert_with(1, || 1), 1);
+    m.compute(2, |_| Some(2));
+    assert_eq!(m.compute(1, |_| None), Some(1));
+    assert_eq!(m.update(&2, |v| *v), Some(2));
+    assert_eq!(m.compare_and_swap(&2, &3, 4), Err(4));
+    assert_eq!(m.len(), 1);
+}
+
+#[test]
+pub fn test_bulk_ops() {
+    let m = SyncIndexMap::<i32, i32>::new();
+    m.extend((0..10).map(|i| (i, i)));
+    assert_eq!(m.insert_many([(1, 10), (20, 20)]), vec![Some(1), None]);
+    m.retain(|k, v| {
+        *v += 1;
+        k % 2 == 0
+    });
+    assert_eq!(m.remove_many(&[0, 1, 2]), vec![1, 3]);
+    let other = SyncIndexMap::new();
+    other.insert(100, 100);
+    m.append(&other);
+    assert!(other.is_empty());
+    let mut drained: Vec<_> = m.drain().collect();
+    drained.sort();
+    assert_eq!(drained, vec![(4, 5), (6, 7), (8, 9), (20, 21), (100, 100)]);
+    assert!(m.is_empty());
+}
+
+#[test]
+pub fn test_positional_ops() {
+    let m = SyncIndexMap::<&'static str, i32>::new();
+    m.extend([("a", 1), ("b", 2), ("c", 3)]);
+    let keys = |m: &SyncIndexMap<&'static str, i32>| m.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+
+    let e = m.get_index(1).unwrap();
+    assert_eq!((*e.key(), *e), ("b", 2));
+    drop(e);
+    assert!(m.get_index(3).is_none());
+    assert_eq!(m.get_index_of("c"), Some(2));
+    let (i, e) = m.get_full("c").unwrap();
+    assert_eq!((i, *e.value()), (2, 3));
+    drop(e);
+    *m.get_index_mut(0).unwrap() = 10;
+    assert_eq!(*m.first().unwrap(), 10);
+    assert_eq!(*m.last().unwrap().key(), "c");
+
+    assert_eq!(m.shift_insert(1, "x", 0), None);
+    assert_eq!(keys(&m), ["a", "x", "b", "c"]);
+    m.move_index(0, 3);
+    assert_eq!(keys(&m), ["x", "b", "c", "a"]);
+    m.swap_indices(0, 1);
+    assert_eq!(keys(&m), ["b", "x", "c", "a"]);
+    m.sort_keys();
+    assert_eq!(keys(&m), ["a", "b", "c", "x"]);
+    m.sort_by(|_, v1, _, v2| v1.cmp(v2));
+    assert_eq!(keys(&m), ["x", "b", "c", "a"]);
+    m.reverse();
+    assert_eq!(keys(&m), ["a", "c", "b", "x"]);
+
+    assert_eq!(m.shift_remove("a"), Some(10));
+    assert_eq!(keys(&m), ["c", "b", "x"]);
+    assert_eq!(m.swap_remove("c"), Some(3));
+    assert_eq!(keys(&m), ["x", "b"]);
+    assert_eq!(m.shift_remove_index(0), Some(("x", 0)));
+    assert_eq!(m.swap_remove_index(5), None);
+    m.extend([("d", 4), ("e", 5)]);
+    assert_eq!(m.pop(), Some(("e", 5)));
+    m.truncate(1);
+    assert_eq!(keys(&m), ["b"]);
+}
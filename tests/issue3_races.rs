@@ -6,7 +6,7 @@
 //! access is now synchronised (per-thread reader slots + writer waits), so
 //! these run race-free.
 
-use dark_std::sync::{SyncBtreeMap, SyncHashMap, SyncIndexMap, SyncVec};
+use dark_std::sync::{LeftRightHashMap, SyncBtreeMap, SyncHashMap, SyncIndexMap, SyncVec};
 
 #[test]
 fn sync_btree_map_race() {
@@ -53,3 +53,80 @@ fn sync_index_map_race() {
         map.insert(true, true);
     });
 }
+
+// `Send` guards pin the shared counter and may be released on another thread
+// than the one that took them.
+#[test]
+fn sync_hash_map_send_guard_race() {
+    let map: SyncHashMap<bool, bool> = SyncHashMap::new();
+    map.insert(true, true);
+    let g = map.get_send(&true);
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            drop(g);
+        });
+        s.spawn(|| {
+            map.get_send(&true);
+        });
+        map.insert(true, false);
+    });
+}
+
+#[test]
+fn sync_vec_iter_race() {
+    let vec: SyncVec<bool> = SyncVec::new();
+    vec.push(true);
+    let it = vec.iter_send();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            it.count();
+        });
+        vec.push(false);
+    });
+}
+
+// Left-right readers never wait: the writer replays its log on the copy they
+// were reading only after they leave.
+#[test]
+fn left_right_hash_map_race() {
+    let map: LeftRightHashMap<bool, bool> = LeftRightHashMap::new();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            map.get(&true);
+            map.get(&true);
+        });
+        map.insert(true, true);
+        map.insert(true, false);
+    });
+}
+
+// A snapshot outlives the reader slot it was taken under: the writer copies
+// the map instead of mutating it under the snapshot.
+#[test]
+fn sync_hash_map_snapshot_race() {
+    let map: SyncHashMap<bool, bool> = SyncHashMap::new();
+    map.insert(true, true);
+    let snap = map.snapshot();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            snap.get(&true);
+        });
+        map.insert(true, false);
+    });
+}
+
+// The guard returned after an insert comes from a downgraded write lock.
+#[test]
+fn sync_hash_map_get_or_insert_race() {
+    let map: SyncHashMap<bool, bool> = SyncHashMap::new();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            map.get(&true);
+        });
+        s.spawn(|| {
+            map.insert(true, false);
+        });
+        let g = map.get_or_insert_with(true, || true);
+        let _ = *g;
+    });
+}
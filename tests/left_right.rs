@@ -0,0 +1,167 @@
+use dark_std::sync::{set_deadlock_policy, DeadlockPolicy, LeftRightBtreeMap, LeftRightHashMap};
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Barrier;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[test]
+pub fn test_left_right_hash_map() {
+    let m = LeftRightHashMap::new();
+    assert_eq!(m.insert(1, "a"), None);
+    assert_eq!(m.insert(1, "b"), Some("a"));
+    m.insert(2, "c");
+    assert_eq!(*m.get(&1).unwrap(), "b");
+    assert!(m.contains_key(&2));
+    assert_eq!(m.len(), 2);
+    assert_eq!(m.remove(&2), Some("c"));
+    assert_eq!(m.remove(&2), None);
+    assert_eq!(m.iter().count(), 1);
+    m.clear();
+    assert!(m.is_empty());
+    // both copies took every change
+    m.insert(3, "d");
+    m.insert(4, "e");
+    assert_eq!(m.read().len(), 2);
+    assert_eq!(m.into_inner().len(), 2);
+}
+
+#[test]
+pub fn test_left_right_btree_map() {
+    let m = LeftRightBtreeMap::from(BTreeMap::from([(2, 2), (1, 1)]));
+    m.insert(3, 3);
+    m.remove(&2);
+    assert_eq!(m.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![1, 3]);
+    let r = m.write(|b| {
+        b.insert(4, 4);
+        b.remove(&1);
+        b.len()
+    });
+    assert_eq!(r, 2);
+    assert_eq!(format!("{:?}", m), "{3: 3, 4: 4}");
+    assert_eq!(m.clone().into_inner(), m.into_inner());
+}
+
+#[test]
+pub fn test_left_right_reads_do_not_wait() {
+    let m = LeftRightHashMap::new();
+    m.insert(1, 1);
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            m.write(|b| {
+                b.insert(1, 2);
+                barrier.wait();
+                // readers keep reading the published copy meanwhile
+                sleep(Duration::from_millis(100));
+            });
+        });
+        barrier.wait();
+        assert_eq!(*m.get(&1).unwrap(), 1);
+    });
+    assert_eq!(*m.get(&1).unwrap(), 2);
+}
+
+#[test]
+pub fn test_left_right_batch_is_atomic() {
+    let m = LeftRightHashMap::new();
+    m.insert(0, 0);
+    m.insert(1, 0);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for i in 1..1000 {
+                m.write(|b| {
+                    b.insert(0, i);
+                    b.insert(1, i);
+                });
+            }
+        });
+        for _ in 0..1000 {
+            let r = m.read();
+            assert_eq!(r[&0], r[&1]);
+        }
+    });
+    assert_eq!(*m.get(&1).unwrap(), 999);
+}
+
+#[test]
+pub fn test_left_right_deadlock() {
+    set_deadlock_policy(DeadlockPolicy::Panic);
+    let m = LeftRightHashMap::new();
+    m.insert(1, 1);
+    let g = m.get(&1);
+    let r = catch_unwind(AssertUnwindSafe(|| m.insert(1, 2)));
+    let msg = r.unwrap_err().downcast::<String>().unwrap();
+    assert!(msg.contains("LeftRightHashMap"), "{}", msg);
+    drop(g);
+    assert_eq!(*m.get(&1).unwrap(), 1);
+}
+
+// A panicking batch is not published, and the copy it half changed is reset:
+// the following writes, which flip between the copies, never resurrect it.
+#[test]
+pub fn test_left_right_panicking_batch() {
+    let m = LeftRightHashMap::new();
+    m.insert(1, 1);
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        m.write(|b| {
+            b.insert(2, 2);
+            panic!("abort");
+        })
+    }));
+    assert!(r.is_err());
+    for k in 3..5 {
+        assert!(!m.contains_key(&2));
+        m.insert(k, k);
+    }
+    assert!(!m.contains_key(&2));
+    assert_eq!(m.len(), 3);
+}
+
+// A write made from a destructor while an unrelated panic unwinds completes,
+// so it is published like any other.
+#[test]
+pub fn test_left_right_write_during_unwind() {
+    struct InsertOnDrop<'a>(&'a LeftRightHashMap<i32, i32>);
+    impl Drop for InsertOnDrop<'_> {
+        fn drop(&mut self) {
+            self.0.insert(1, 1);
+        }
+    }
+    let m = LeftRightHashMap::new();
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        let _g = InsertOnDrop(&m);
+        panic!("unrelated");
+    }));
+    assert!(r.is_err());
+    assert_eq!(*m.get(&1).unwrap(), 1);
+    m.insert(2, 2);
+    assert_eq!(m.len(), 2);
+}
+
+#[test]
+pub fn test_left_right_bulk_ops() {
+    let m = LeftRightBtreeMap::new();
+    m.extend((0..10).map(|i| (i, i)));
+    assert_eq!(m.insert_many([(1, 10), (20, 20)]), vec![Some(1), None]);
+    m.retain(|k, _| k % 2 == 0);
+    assert_eq!(m.remove_many(&[0, 1, 2]), vec![0, 2]);
+    let other = LeftRightBtreeMap::new();
+    other.insert(100, 100);
+    m.append(&other);
+    assert!(other.is_empty());
+    // both copies saw the same changes
+    m.insert(6, 60);
+    let drained: Vec<_> = m.drain().into_iter().collect();
+    assert_eq!(drained, vec![(4, 4), (6, 60), (8, 8), (20, 20), (100, 100)]);
+    assert!(m.is_empty());
+    m.insert(1, 1);
+    assert_eq!(m.into_inner(), BTreeMap::from([(1, 1)]));
+
+    let m = LeftRightHashMap::new();
+    m.extend((0..4).map(|i| (i, i)));
+    m.retain(|k, _| *k > 1);
+    assert_eq!(m.len(), 2);
+    m.insert(2, 20);
+    assert_eq!(*m.get(&2).unwrap(), 20);
+}
@@ -0,0 +1,70 @@
+use dark_std::sync::{Overflow, SyncRingBuffer};
+use std::sync::Barrier;
+
+#[test]
+pub fn test_ring_buffer_evicts_oldest() {
+    let b = SyncRingBuffer::new(3);
+    assert_eq!(b.max_len(), 3);
+    assert_eq!(b.overflow(), Overflow::EvictOldest);
+    for i in 0..3 {
+        assert_eq!(b.push(i), None);
+    }
+    assert!(b.is_full());
+    assert_eq!(b.push(3), Some(0));
+    assert_eq!(*b.front().unwrap(), 1);
+    assert_eq!(*b.back().unwrap(), 3);
+    assert_eq!(*b.get(1).unwrap(), 2);
+    assert!(b.get(3).is_none());
+    assert_eq!(b.pushes([4, 5]), vec![1, 2]);
+    assert_eq!(b.iter().rev().copied().collect::<Vec<_>>(), vec![5, 4, 3]);
+    assert_eq!(format!("{:?}", b), "[3, 4, 5]");
+    assert_eq!(b.clone().to_vec(), vec![3, 4, 5]);
+    assert_eq!(b.pop_front(), Some(3));
+    assert_eq!(b.pop_back(), Some(5));
+    assert_eq!(b.len(), 1);
+    b.clear();
+    assert!(b.is_empty());
+
+    let none = SyncRingBuffer::new(0);
+    assert_eq!(none.push(1), Some(1));
+    assert!(none.is_empty());
+}
+
+#[test]
+pub fn test_ring_buffer_rejects() {
+    let b = SyncRingBuffer::with_overflow(2, Overflow::Reject);
+    assert_eq!(b.push(1), None);
+    assert_eq!(b.push(2), None);
+    assert_eq!(b.push(3), Some(3));
+    assert_eq!(b.pushes([4, 5]), vec![4, 5]);
+    assert_eq!(b.pop_front(), Some(1));
+    assert_eq!(b.pushes([6, 7]), vec![7]);
+    assert_eq!(b.into_inner(), [2, 6]);
+}
+
+#[test]
+pub fn test_ring_buffer_concurrent() {
+    let b = SyncRingBuffer::new(16);
+    let barrier = Barrier::new(4);
+    std::thread::scope(|s| {
+        for t in 0..2 {
+            let (b, barrier) = (&b, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..1000 {
+                    b.push(t * 1000 + i);
+                }
+            });
+        }
+        for _ in 0..2 {
+            let (b, barrier) = (&b, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for _ in 0..1000 {
+                    assert!(b.iter().count() <= 16);
+                }
+            });
+        }
+    });
+    assert_eq!(b.len(), 16);
+}
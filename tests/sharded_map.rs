@@ -0,0 +1,66 @@
+use dark_std::sync::ShardedSyncHashMap;
+
+mod common;
+
+#[test]
+pub fn test_sharded_map() {
+    let m = ShardedSyncHashMap::with_shard_amount(3);
+    assert_eq!(m.shards().len(), 4);
+    for i in 0..100 {
+        assert_eq!(m.insert(i, i), None);
+    }
+    assert_eq!(m.insert(1, 10), Some(1));
+    assert_eq!(*m.get(&1).unwrap(), 10);
+    *m.get_mut(&2).unwrap() = 20;
+    assert_eq!(*m.get(&2).unwrap(), 20);
+    assert_eq!(m.remove(&3), Some(3));
+    assert!(!m.contains_key(&3));
+    assert_eq!(m.len(), 99);
+    let mut keys: Vec<_> = m.iter().map(|(k, _)| *k).collect();
+    keys.sort();
+    assert_eq!(keys.len(), 99);
+    assert_eq!(m.shards().iter().map(|s| s.len()).sum::<usize>(), 99);
+    assert_eq!(m.into_inner().len(), 99);
+}
+
+#[test]
+pub fn test_sharded_map_parallel_writes() {
+    let m = ShardedSyncHashMap::with_shard_amount(16);
+    m.insert(0, 0);
+    // a reader in one shard does not hold back writers to the others
+    let k = (1..)
+        .find(|k| !std::ptr::eq(m.shard(k), m.shard(&0)))
+        .unwrap();
+    common::with_reader_elsewhere(
+        || m.get(&0).unwrap(),
+        || {
+            m.shard(&k).try_insert(k, k).unwrap();
+            assert!(m.shard(&0).try_insert(0, 1).is_err());
+        },
+    );
+}
+
+#[test]
+pub fn test_sharded_bulk_ops() {
+    let m = ShardedSyncHashMap::with_shard_amount(4);
+    m.extend((0..100).map(|i| (i, i)));
+    assert_eq!(m.len(), 100);
+    assert_eq!(
+        m.insert_many([(5, 50), (200, 200), (7, 70)]),
+        vec![Some(5), None, Some(7)]
+    );
+    m.retain(|k, _| k % 2 == 1);
+    assert_eq!(m.len(), 50);
+    let mut removed = m.remove_many(&[1, 2, 5, 7]);
+    removed.sort();
+    assert_eq!(removed, vec![1, 50, 70]);
+    let other = ShardedSyncHashMap::with_shard_amount(2);
+    other.insert(1000, 1000);
+    m.append(&other);
+    assert!(other.is_empty());
+    let mut drained: Vec<_> = m.drain().collect();
+    drained.sort();
+    assert_eq!(drained.len(), 48);
+    assert_eq!(drained.last(), Some(&(1000, 1000)));
+    assert!(m.is_empty());
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "stats")]
+
+use dark_std::sync::{SyncCell, SyncHashMap, SyncVec};
+use std::sync::Barrier;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[test]
+pub fn test_stats_counts_reads_and_writes() {
+    let m = SyncHashMap::<i32, i32>::new();
+    m.insert(1, 1);
+    m.insert(2, 2);
+    assert_eq!(*m.get(&1).unwrap(), 1);
+    assert_eq!(m.len(), 2);
+    let s = m.stats();
+    assert_eq!(s.writes, 2);
+    assert_eq!(s.reads, 2);
+    assert_eq!(s.read_retries, 0);
+    assert_eq!(s.active_reader_slots, 0);
+    assert_eq!(s.registry_slots, 1);
+
+    let g = m.get(&1).unwrap();
+    assert_eq!(m.stats().active_reader_slots, 1);
+    drop(g);
+}
+
+// A writer waiting on a reader shows up in the wait time, and a reader
+// arriving during the write is counted as a retry.
+#[test]
+pub fn test_stats_write_wait() {
+    let v = SyncVec::<i32>::new();
+    v.push(1);
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let g = v.get(0).unwrap();
+            barrier.wait();
+            sleep(Duration::from_millis(50));
+            drop(g);
+        });
+        barrier.wait();
+        let mut w = v.get_mut(0).unwrap();
+        s.spawn(|| v.len());
+        sleep(Duration::from_millis(50));
+        *w = 2;
+    });
+    let s = v.stats();
+    assert_eq!(s.writes, 2);
+    assert!(s.write_wait_max >= Duration::from_millis(40), "{:?}", s);
+    assert!(s.write_wait_total >= s.write_wait_max);
+    assert!(s.read_retries >= 1, "{:?}", s);
+    // Exited threads return their slot, so the `len` reader may reuse it.
+    assert!(s.registry_slots >= 1);
+}
+
+#[test]
+pub fn test_cell_stats() {
+    let c = SyncCell::new(0);
+    *c.write() += 1;
+    assert_eq!(*c.read(), 1);
+    let s = c.stats();
+    assert_eq!((s.reads, s.writes), (1, 1));
+}
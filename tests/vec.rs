@@ -1,5 +1,6 @@
 use dark_std::sync::SyncVec;
 use dark_std::sync_vec;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
@@ -22,7 +23,7 @@ pub fn test_empty() {
 pub fn test_push() {
     let m = SyncVec::<i32>::new();
     let insert = m.push(1);
-    assert_eq!(insert.is_none(), true);
+    assert!(insert.is_none());
 }
 
 #[test]
@@ -79,8 +80,8 @@ pub fn test_remove() {
     let rm = m.remove(0).unwrap();
     println!("rm:{:?}", rm);
     drop(rm);
-    assert_eq!(true, m.is_empty());
-    assert_eq!(true, m.dirty_ref().is_empty());
+    assert!(m.is_empty());
+    assert!(m.dirty_ref().is_empty());
     assert_eq!(None, m.get(0));
 }
 
@@ -159,7 +160,7 @@ pub async fn test_iter_send_across_await() {
     let v2 = v.clone();
     let sum = tokio::spawn(async move {
         let mut sum = 0;
-        for item in v2.iter() {
+        for item in v2.iter_send() {
             // keep the iterator alive across an await
             tokio::task::yield_now().await;
             sum += *item;
@@ -171,11 +172,11 @@ pub async fn test_iter_send_across_await() {
     assert_eq!(sum, 6);
 }
 
-// Compile-time guard: the read iterator must be `Send` for `V: Sync`.
+// Compile-time guard: the `Send` read iterator must be `Send` for `V: Sync`.
 #[test]
 pub fn test_vec_iter_is_send() {
     fn assert_send<T: Send>() {}
-    assert_send::<dark_std::sync::VecIter<'static, i32>>();
+    assert_send::<dark_std::sync::VecIterSend<'static, i32>>();
 }
 
 // `&SyncVec` must implement `IntoIterator` (the pattern `for x in &v`, used
@@ -240,3 +241,148 @@ pub fn test_iter_mut_deref() {
     assert_eq!(it.len(), 3); // via Deref to slice::IterMut
     assert_eq!(it.next(), Some(&mut 1));
 }
+
+#[tokio::test]
+async fn test_push_async() {
+    let v = SyncVec::<i32>::new_arc();
+    let v2 = v.clone();
+    tokio::spawn(async move {
+        v2.push_async(1).await;
+        v2.insert_async(0, 0).await;
+    })
+    .await
+    .unwrap();
+    assert_eq!(v.read_async(|v| v.clone()).await, vec![0, 1]);
+    *v.get_mut_async(1).await.unwrap() = 2;
+    v.write_async(|v| v.push(3)).await;
+    assert_eq!(v.remove_async(0).await, Some(0));
+    assert_eq!(*v.dirty_ref(), vec![2, 3]);
+}
+
+#[test]
+pub fn test_try_push() {
+    let v = SyncVec::<i32>::new();
+    v.try_push(1).unwrap();
+    let g = v.get(0).unwrap();
+    assert!(v.try_push(2).is_err());
+    assert!(v.try_pop().is_err());
+    drop(g);
+    v.try_push(2).unwrap();
+    assert_eq!(v.try_pop().unwrap(), Some(2));
+    assert_eq!(v.len(), 1);
+}
+
+#[test]
+pub fn test_push_timeout() {
+    let v = SyncVec::<i32>::new();
+    v.push(1);
+    let g = v.get(0).unwrap();
+    assert!(v.push_timeout(2, Duration::from_millis(20)).is_err());
+    drop(g);
+    v.push_timeout(2, Duration::from_millis(20)).unwrap();
+    assert_eq!(v.len(), 2);
+}
+
+#[test]
+pub fn test_get_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<dark_std::sync::SendReadGuard<'static, i32>>();
+    let v = SyncVec::<i32>::new();
+    v.push(1);
+    let g = v.get_send(0).unwrap();
+    std::thread::scope(|s| {
+        s.spawn(move || assert_eq!(*g, 1));
+    });
+    v.push(2);
+    assert_eq!(v.dirty_ref_send().len(), 2);
+}
+
+#[test]
+pub fn test_get_owned() {
+    let v = Arc::new(SyncVec::<i32>::new());
+    v.push(1);
+    v.push(2);
+    let g = v.get_owned(1).unwrap();
+    let it = v.iter_owned();
+    let all = v.read_owned();
+    let h = std::thread::spawn(move || (*g, it.collect::<Vec<_>>(), all.len()));
+    assert_eq!(h.join().unwrap(), (2, vec![1, 2], 2));
+    v.push(3);
+}
+
+#[test]
+pub fn test_transact_rollback() {
+    let v = sync_vec![1, 2, 3];
+    let r: Result<(), _> = v.transact(|tx| {
+        tx.push(4);
+        tx.remove(0);
+        tx.insert(1, 9);
+        assert_eq!(tx.set(0, 7), 2);
+        *tx.get_mut(2).unwrap() = 8;
+        tx.pop();
+        Err(dark_std::err!("abort"))
+    });
+    assert!(r.is_err());
+    assert_eq!(*v.dirty_ref(), vec![1, 2, 3]);
+    v.transact(|tx| {
+        tx.push(4);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(v.write(|v| v.len()), 4);
+}
+
+#[test]
+pub fn test_slice_ops() {
+    let v = SyncVec::from(vec![3, 1, 2, 2, 5, 4]);
+    assert_eq!(*v.first().unwrap(), 3);
+    assert_eq!(*v.last().unwrap(), 4);
+    v.sort();
+    assert_eq!(*v.as_slice(), [1, 2, 2, 3, 4, 5]);
+    assert_eq!(v.binary_search(&4), Ok(4));
+    assert_eq!(v.binary_search_by_key(&6, |x| x * 2), Ok(3));
+    v.dedup();
+    assert_eq!(*v.as_slice(), [1, 2, 3, 4, 5]);
+    v.sort_by(|a, b| b.cmp(a));
+    assert_eq!(*v.as_slice(), [5, 4, 3, 2, 1]);
+    v.sort_by_key(|x| x % 3);
+    assert_eq!(*v.as_slice(), [3, 4, 1, 5, 2]);
+    v.sort_unstable();
+    v.retain(|x| x % 2 == 1);
+    assert_eq!(*v.as_slice(), [1, 3, 5]);
+    v.extend_from_slice(&[7, 9]);
+    v.swap(0, 4);
+    assert_eq!(*v.get_range(..2).unwrap(), [9, 3]);
+    assert!(v.get_range(4..6).is_none());
+    v.rotate_left(1);
+    assert_eq!(*v.as_slice(), [3, 5, 7, 1, 9]);
+    v.rotate_right(2);
+    assert_eq!(*v.as_slice(), [1, 9, 3, 5, 7]);
+    assert_eq!(v.drain(1..3).collect::<Vec<_>>(), vec![9, 3]);
+    let tail = v.split_off(2);
+    assert_eq!(tail.into_inner(), vec![7]);
+    v.resize(4, 0);
+    assert_eq!(*v.as_slice(), [1, 5, 0, 0]);
+    v.truncate(1);
+    assert_eq!(v.into_inner(), vec![1]);
+}
+
+#[test]
+pub fn test_set_and_fallible_index_writes() {
+    let v = SyncVec::from(vec![1, 2]);
+    assert_eq!(v.set(0, 10), Some(1));
+    let set_out_of_range = std::panic::catch_unwind(AssertUnwindSafe(|| v.set(2, 30)));
+    assert!(set_out_of_range.is_err());
+    assert_eq!(v.try_set(1, 20).unwrap(), Some(2));
+    let e = v.try_set(2, 30).unwrap_err().to_string();
+    assert!(e.starts_with("OutOfRange"), "{}", e);
+    let insert_out_of_range = std::panic::catch_unwind(AssertUnwindSafe(|| v.insert(3, 30)));
+    assert!(insert_out_of_range.is_err());
+    assert!(v.try_insert(3, 30).is_err());
+    assert!(v.try_insert(2, 30).unwrap().is_none());
+    assert!(v.insert_timeout(4, 40, Duration::from_millis(20)).is_err());
+    v.insert_timeout(0, 0, Duration::from_millis(20)).unwrap();
+    // the failed writes released the writer lock
+    v.push(40);
+    assert_eq!(v.into_inner(), vec![0, 10, 20, 30, 40]);
+}
@@ -0,0 +1,92 @@
+use dark_std::sync::SyncVecDeque;
+use std::collections::VecDeque;
+use std::sync::Barrier;
+
+mod common;
+
+#[test]
+pub fn test_vec_deque() {
+    let q = SyncVecDeque::new();
+    assert!(q.is_empty());
+    q.push_back(2);
+    q.push_back(3);
+    q.push_front(1);
+    assert_eq!(q.len(), 3);
+    assert_eq!(*q.front().unwrap(), 1);
+    assert_eq!(*q.back().unwrap(), 3);
+    assert_eq!(*q.get(1).unwrap(), 2);
+    assert!(q.get(3).is_none());
+    *q.front_mut().unwrap() = 10;
+    *q.get_mut(1).unwrap() += 10;
+    for v in q.iter_mut() {
+        *v += 1;
+    }
+    assert_eq!(q.iter().rev().copied().collect::<Vec<_>>(), vec![4, 13, 11]);
+    assert!(q.contains(&13));
+    assert_eq!(format!("{:?}", q), "[11, 13, 4]");
+    assert_eq!(q.clone(), q);
+    q.insert(1, 12);
+    assert_eq!(q.remove(0), Some(11));
+    assert_eq!(q.remove(5), None);
+    q.extend([5, 6]);
+    assert_eq!(q.pop_front(), Some(12));
+    assert_eq!(q.pop_back(), Some(6));
+    q.try_push_back(7).unwrap();
+    assert_eq!(q.try_pop_front().unwrap(), Some(13));
+    assert_eq!(q.write(|d| d.drain(..).collect::<Vec<_>>()), vec![4, 5, 7]);
+    assert_eq!(q.into_inner(), VecDeque::new());
+}
+
+#[test]
+pub fn test_vec_deque_try_write_would_block() {
+    let q = SyncVecDeque::from(vec![1]);
+    common::with_reader_elsewhere(
+        || q.front().unwrap(),
+        || {
+            assert!(q.try_push_back(2).is_err());
+            assert!(q.try_push_front(0).is_err());
+            assert!(q.try_pop_front().is_err());
+            assert!(q.try_pop_back().is_err());
+        },
+    );
+    q.try_push_back(2).unwrap();
+    q.try_push_front(0).unwrap();
+    assert_eq!(q.try_pop_back().unwrap(), Some(2));
+    assert_eq!(q.into_iter().collect::<Vec<_>>(), vec![0, 1]);
+}
+
+#[test]
+pub fn test_vec_deque_mut_ends() {
+    let mut q = SyncVecDeque::new();
+    q.push_back_mut(2);
+    q.push_front_mut(1);
+    q.push_back_mut(3);
+    assert_eq!(q.pop_back_mut(), Some(3));
+    assert_eq!(q.pop_front_mut(), Some(1));
+    assert_eq!(q.into_inner(), VecDeque::from(vec![2]));
+}
+
+#[test]
+pub fn test_vec_deque_fifo() {
+    let q = SyncVecDeque::new();
+    let barrier = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            barrier.wait();
+            for i in 0..1000 {
+                q.push_back(i);
+            }
+        });
+        s.spawn(|| {
+            barrier.wait();
+            let mut next = 0;
+            while next < 1000 {
+                if let Some(v) = q.pop_front() {
+                    assert_eq!(v, next);
+                    next += 1;
+                }
+            }
+        });
+    });
+    assert!(q.is_empty());
+}